@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use actix::clock::interval;
+use log::info;
+use serde::Deserialize;
+use tetris_core::{
+    net::Message,
+    tetris::{Bot, Difficulty, Event, Game, GameConfig},
+};
+use tokio::sync::mpsc::{UnboundedReceiver, error::TryRecvError};
+
+use crate::proto::TetrisSocket;
+
+/// The bot runs at 60 frames per second like the clients
+static FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+/// Frames between sending the board of the bot, the same as the clients
+const SHARE_INTERVAL: u32 = 15;
+
+/// Difficulty of a bot opponent, selected in the url of a bot game
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum BotLevel {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl BotLevel {
+    pub const fn difficulty(self) -> Difficulty {
+        match self {
+            BotLevel::Easy => Difficulty::EASY,
+            BotLevel::Medium => Difficulty::MEDIUM,
+            BotLevel::Hard => Difficulty::HARD,
+        }
+    }
+}
+
+/// Plays a game against `player` on the server. Messages of the player are received through
+/// `inbox`, the bot stops once it is closed or either side lost.
+pub async fn run_bot(
+    mut player: TetrisSocket,
+    config: GameConfig,
+    level: BotLevel,
+    mut inbox: UnboundedReceiver<Message>,
+) {
+    info!("Bot started with {level:?}");
+    let mut game = Game::new(config);
+    let mut bot = Bot::new(level.difficulty(), None);
+    let mut interval = interval(FRAME);
+    loop {
+        interval.tick().await;
+        loop {
+            match inbox.try_recv() {
+                Ok(Message::LineSend(lines)) => game.accumulate_garbage(lines),
+                Ok(Message::Gameover | Message::Disconnect) | Err(TryRecvError::Disconnected) => {
+                    info!("Bot stopped");
+                    return;
+                }
+                Ok(_) => {}
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+
+        let actions = bot.actions(&game);
+        game.user_actions(actions);
+        for event in &game.events {
+            if let Event::Attack(lines) = event {
+                player.line_send(*lines).await;
+            }
+        }
+        if game.done {
+            info!("Bot lost");
+            let _ = player.send(&Message::Gameover).await;
+            return;
+        }
+        if game.frame.is_multiple_of(SHARE_INTERVAL) {
+            let mut board = game.board.clone();
+            board.place(&game.piece);
+            if player
+                .send(&Message::GameState(board.into()))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
@@ -3,8 +3,9 @@ use actix_ws::Session;
 use log::warn;
 use serde::Serialize;
 use std::fmt::Debug;
+use tokio::sync::mpsc::UnboundedSender;
 
-use crate::proto::TetrisSocket;
+use crate::{bot::BotLevel, proto::TetrisSocket, seed::SeedIssuer};
 use tetris_core::{
     net::Message,
     tetris::{GameConfig, GameSettings},
@@ -33,6 +34,21 @@ pub enum Game {
         id: String,
         config: GameConfig,
     },
+    /// A game against a bot on the server, waiting for the player to connect
+    BotReady {
+        p1_id: String,
+        id: String,
+        settings: GameSettings,
+        level: BotLevel,
+    },
+    /// The player plays against the bot task, which gets the messages of the player through
+    /// `bot`
+    BotRunning {
+        p1: TetrisSocket,
+        bot: UnboundedSender<Message>,
+        id: String,
+        config: GameConfig,
+    },
 }
 
 impl Debug for Game {
@@ -41,6 +57,8 @@ impl Debug for Game {
             Game::Waiting { id, .. } => f.debug_struct("Waiting").field("id", id).finish(),
             Game::Ready { id, .. } => f.debug_struct("Ready").field("id", id).finish(),
             Game::Running { id, .. } => f.debug_struct("Running").field("id", id).finish(),
+            Game::BotReady { id, .. } => f.debug_struct("BotReady").field("id", id).finish(),
+            Game::BotRunning { id, .. } => f.debug_struct("BotRunning").field("id", id).finish(),
         }
     }
 }
@@ -66,28 +84,40 @@ impl Game {
         } else if let Self::Running { p1, p2, .. } = self {
             p1.clone().canceled("timeout").await;
             p2.clone().canceled("timeout").await;
+        } else if let Self::BotRunning { p1, .. } = self {
+            p1.clone().canceled("timeout").await;
         }
     }
 
     pub fn get_id(&self) -> &String {
         match self {
-            Game::Waiting { id, .. } | Game::Ready { id, .. } | Game::Running { id, .. } => id,
+            Game::Waiting { id, .. }
+            | Game::Ready { id, .. }
+            | Game::Running { id, .. }
+            | Game::BotReady { id, .. }
+            | Game::BotRunning { id, .. } => id,
         }
     }
 
     pub fn get_settings(&self) -> &GameSettings {
         match &self {
-            Game::Waiting { settings, .. } | Game::Ready { settings, .. } => settings,
-            Game::Running { config, .. } => &config.settings,
+            Game::Waiting { settings, .. }
+            | Game::Ready { settings, .. }
+            | Game::BotReady { settings, .. } => settings,
+            Game::Running { config, .. } | Game::BotRunning { config, .. } => &config.settings,
         }
     }
 
     pub async fn recv(&mut self, msg: &Bytes, player_id: &str) {
-        let (_this, other) = self.get_sockets(player_id);
         let Ok(message) = serde_cbor::from_slice(msg) else {
             warn!("Invalid message received from Websocket");
             return;
         };
+        if let Game::BotRunning { bot, .. } = self {
+            let _ = bot.send(message);
+            return;
+        }
+        let (_this, other) = self.get_sockets(player_id);
         match message {
             Message::LineSend(lines) => other.line_send(lines).await,
 
@@ -101,10 +131,17 @@ impl Game {
         }
     }
 
-    pub async fn start(&mut self) {
+    /// Sends the config to the players, each with their own token for the seed
+    pub async fn start(&mut self, seeds: &SeedIssuer) {
+        let start = |config: &GameConfig| {
+            let seed = config.seed.unwrap_or_default();
+            Message::Start(config.clone(), seeds.token(&seed))
+        };
         if let Game::Running { p1, p2, config, .. } = self {
-            let _ = p1.send(&Message::Start(*config)).await;
-            let _ = p2.send(&Message::Start(*config)).await;
+            let _ = p1.send(&start(config)).await;
+            let _ = p2.send(&start(config)).await;
+        } else if let Game::BotRunning { p1, config, .. } = self {
+            let _ = p1.send(&start(config)).await;
         }
     }
 
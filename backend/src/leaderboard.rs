@@ -1,11 +1,17 @@
 use std::{cmp::Ordering, collections::BTreeSet};
 
-use actix_web::{HttpResponse, Responder};
+use actix_web::{HttpResponse, Responder, web};
 use serde::{Deserialize, Serialize};
-use tetris_core::{net::HighscoreReq, tetris::GameSettings};
+use tetris_core::{
+    net::HighscoreReq,
+    tetris::{AttackTable, GameMode, GameSettings, GarbageConfig, ReplayPlayer, Ruleset},
+};
 use tokio::sync::Mutex;
 
-use crate::{STORE_TOKEN, Store, auth::try_auth};
+use crate::{STORE_TOKEN, Store, auth::try_auth, seed::SeedIssuer};
+
+/// Longest replay that is re-simulated, two hours at 60 frames per second
+const MAX_REPLAY_FRAMES: u32 = 2 * 60 * 60 * 60;
 
 #[derive(Debug)]
 pub struct Leaderboard {
@@ -26,27 +32,95 @@ impl Leaderboard {
 
     /// Attempts to add an entry to the leaderboard. Returns if the entry was actually added.
     /// Otherwise, a 400 should be sent
-    pub async fn add_entry(&self, req: HighscoreReq, store: &Store) -> HttpResponse {
+    pub async fn add_entry(
+        &self,
+        req: HighscoreReq,
+        store: &Store,
+        seeds: &SeedIssuer,
+    ) -> HttpResponse {
         if !try_auth(&req) {
             return HttpResponse::Unauthorized().finish();
         }
 
-        if req.settings.easy {
+        let settings = req.replay.config.settings;
+        if settings.easy {
             return HttpResponse::BadRequest()
                 .body("Games in easy mode are not eligable for a highscore");
         }
+        let config = &req.replay.config;
+        if config.ruleset != Ruleset::from(settings)
+            || config.attack != AttackTable::default()
+            || config.garbage != GarbageConfig::default()
+        {
+            return HttpResponse::BadRequest()
+                .body("Custom rulesets are not eligable for a highscore");
+        }
+        if !req.was_multiplayer && !req.replay.garbage().is_empty() {
+            return HttpResponse::BadRequest().body("Singleplayer replay received garbage");
+        }
+        if req.replay.frames > MAX_REPLAY_FRAMES {
+            return HttpResponse::BadRequest().body("Replay is too long");
+        }
+
+        let Some(mode) = Mode::from_settings(settings) else {
+            return HttpResponse::BadRequest()
+                .body("Jupiter and NES variants are only eligable in marathon");
+        };
 
         if req.name.len() > 50 {
             return HttpResponse::BadRequest().body("Name is too long");
         }
 
+        let Some(seed) = req.replay.config.seed else {
+            return HttpResponse::BadRequest().body("Replay has no seed");
+        };
+
+        let Some(token) = req.seed_token else {
+            return HttpResponse::BadRequest().body("Missing seed token");
+        };
+        if seeds.verify(&token, &seed).is_none() {
+            return HttpResponse::BadRequest().body("Unknown seed token");
+        }
+
+        let replay = req.replay;
+        let Ok(game) = web::block(move || ReplayPlayer::new(replay).finish()).await else {
+            return HttpResponse::InternalServerError().finish();
+        };
+        if !game.done {
+            return HttpResponse::BadRequest().body("Replay does not end in a game over");
+        }
+        if mode.has_goal() && !game.finished() {
+            return HttpResponse::BadRequest().body("Replay does not reach the goal");
+        }
+        let result = if mode.ranked_by_time() {
+            game.frame
+        } else {
+            game.score
+        };
+        if result != req.score {
+            return HttpResponse::BadRequest().body("Score does not match the replay");
+        }
+        // only redeemed once the replay checked out, so a rejected replay does not use it up
+        if !seeds.redeem(&token, &seed).await {
+            return HttpResponse::BadRequest().body("Seed token was already used");
+        }
+
         let mut board = self.board.lock().await;
+        if mode == Mode::Cheese {
+            board.insert(Entry {
+                score: game.pieces,
+                name: req.name.clone(),
+                was_multiplayer: req.was_multiplayer,
+                was_random: settings.random,
+                mode: Mode::CheesePieces,
+            });
+        }
         board.insert(Entry {
-            score: req.score,
+            score: result,
             name: req.name,
             was_multiplayer: req.was_multiplayer,
-            was_random: req.settings.random,
-            mode: Mode::from_settings(req.settings),
+            was_random: settings.random,
+            mode,
         });
         drop(board);
         store
@@ -73,22 +147,61 @@ impl Leaderboard {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
 enum Mode {
     Normal,
     Jupiter,
     Nes,
     Crazy,
+    Sprint,
+    Ultra,
+    /// Cheese races ranked by time
+    Cheese,
+    /// Cheese races ranked by pieces used, entered together with [`Mode::Cheese`]
+    CheesePieces,
+    /// Marathons that end on level 15
+    Marathon15,
+    Master,
 }
 
 impl Mode {
-    pub const fn from_settings(settings: GameSettings) -> Self {
-        match (settings.jupiter, settings.nes) {
-            (true, true) => Mode::Crazy,
-            (true, false) => Mode::Jupiter,
-            (false, true) => Mode::Nes,
-            (false, false) => Mode::Normal,
-        }
+    /// The leaderboard of a game. Only marathons have boards for the jupiter and NES variants,
+    /// other modes with them return `None`.
+    pub const fn from_settings(settings: GameSettings) -> Option<Self> {
+        let variant = settings.jupiter || settings.nes;
+        let mode = match settings.mode {
+            GameMode::Marathon => match (settings.jupiter, settings.nes) {
+                (true, true) => Mode::Crazy,
+                (true, false) => Mode::Jupiter,
+                (false, true) => Mode::Nes,
+                (false, false) => Mode::Normal,
+            },
+            _ if variant => return None,
+            GameMode::Sprint => Mode::Sprint,
+            GameMode::Ultra => Mode::Ultra,
+            GameMode::Cheese => Mode::Cheese,
+            GameMode::Marathon15 => Mode::Marathon15,
+            GameMode::Master => Mode::Master,
+        };
+        Some(mode)
+    }
+
+    /// If games have to reach the goal of their ruleset to count
+    pub const fn has_goal(&self) -> bool {
+        matches!(
+            self,
+            Mode::Sprint | Mode::Ultra | Mode::Cheese | Mode::Marathon15
+        )
+    }
+
+    /// If the score of entries is the time in frames
+    pub const fn ranked_by_time(&self) -> bool {
+        matches!(self, Mode::Sprint | Mode::Cheese)
+    }
+
+    /// If a lower score is better, like times and piece counts
+    pub const fn lower_is_better(&self) -> bool {
+        self.ranked_by_time() || matches!(self, Mode::CheesePieces)
     }
 }
 
@@ -101,10 +214,17 @@ struct Entry {
     mode: Mode,
 }
 
+/// Entries are sorted by mode first, the best entry of every mode comes last
 impl Ord for Entry {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.score
-            .cmp(&other.score)
+        let score = if self.mode.lower_is_better() {
+            other.score.cmp(&self.score)
+        } else {
+            self.score.cmp(&other.score)
+        };
+        self.mode
+            .cmp(&other.mode)
+            .then(score)
             .then(self.was_multiplayer.cmp(&other.was_multiplayer))
             .then(self.was_random.cmp(&other.was_random))
             .then(self.name.cmp(&other.name))
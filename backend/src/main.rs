@@ -3,6 +3,7 @@ use actix_web::{
     App, Error, HttpRequest, HttpResponse, HttpServer, Responder, get, middleware::Compress, post,
     rt, web,
 };
+use bot::{BotLevel, run_bot};
 use broadcast::Broadcaster;
 use game::Game;
 use leaderboard::Leaderboard;
@@ -11,19 +12,22 @@ use persistent_kv::{Config, PersistentKeyValueStore};
 use proto::TetrisSocket;
 use rand::{Rng, distr::Alphanumeric};
 use replace_with::replace_with_or_abort;
+use seed::SeedIssuer;
 use std::{collections::HashMap, sync::Arc};
 use tetris_core::{
     net::HighscoreReq,
     tetris::{GameConfig, GameSettings, RandomSeed},
 };
-use tokio::sync::Mutex;
-use ws::{ws_running, ws_waiting};
+use tokio::sync::{Mutex, mpsc::unbounded_channel};
+use ws::{bot_ready_timeout, ws_running, ws_waiting};
 
 mod auth;
+mod bot;
 mod broadcast;
 mod game;
 mod leaderboard;
 mod proto;
+mod seed;
 mod ws;
 
 type Store = PersistentKeyValueStore<String, String>;
@@ -36,6 +40,9 @@ static SAVE_PATH: &str = "/home/christian/tetris_leaderboard.store";
 
 static STORE_TOKEN: &str = "b";
 
+/// Maximum size of a highscore request, which includes the whole replay
+const REPLAY_LIMIT: usize = 4 << 20;
+
 struct Games {
     games: Mutex<HashMap<String, Arc<Mutex<Game>>>>,
     broadcaster: Arc<Broadcaster>,
@@ -154,12 +161,90 @@ async fn join(state: web::Data<Games>, path: web::Path<String>) -> impl Responde
     HttpResponse::Ok().json(format!("{game_id}/{p2}"))
 }
 
+/// Creates a game against a bot on the server, the player connects to it like after joining
+#[get("/bot-game/{level}")]
+async fn bot_game(
+    state: web::Data<Games>,
+    level: web::Path<BotLevel>,
+    settings: web::Query<GameSettings>,
+) -> impl Responder {
+    let id = get_id();
+    let p1_id = get_id();
+    let game = Arc::new(Mutex::new(Game::BotReady {
+        p1_id: p1_id.clone(),
+        id: id.clone(),
+        settings: *settings,
+        level: level.into_inner(),
+    }));
+    state.games.lock().await.insert(id.clone(), game);
+    rt::spawn(bot_ready_timeout(state.clone(), id.clone()));
+
+    HttpResponse::Ok().json(format!("{id}/{p1_id}"))
+}
+
+/// Connects the player of a bot game and starts the bot
+async fn connect_bot(
+    request: &HttpRequest,
+    stream: web::Payload,
+    state: &web::Data<Games>,
+    game_arc: &Arc<Mutex<Game>>,
+    game: &mut Game,
+    player_id: String,
+) -> Result<HttpResponse, Error> {
+    let Game::BotReady { p1_id, level, .. } = &*game else {
+        return Ok(HttpResponse::Conflict().finish());
+    };
+    if *p1_id != player_id {
+        info!("Cannot join, no slot free");
+        return Ok(HttpResponse::Conflict().finish());
+    }
+    let level = *level;
+    let (res, session, stream) = actix_ws::handle(request, stream)?;
+    let (bot, inbox) = unbounded_channel();
+    replace_with_or_abort(game, |game| {
+        let Game::BotReady {
+            p1_id,
+            id,
+            settings,
+            ..
+        } = game
+        else {
+            unreachable!()
+        };
+        Game::BotRunning {
+            p1: TetrisSocket::new(session.clone(), p1_id),
+            bot,
+            id,
+            config: game_config(settings),
+        }
+    });
+    let seeds = request
+        .app_data::<web::Data<SeedIssuer>>()
+        .expect("seed issuer is registered with the app");
+    game.start(seeds).await;
+    info!("Starting bot game {}", game.get_id());
+    if let Game::BotRunning { p1, config, .. } = game {
+        rt::spawn(run_bot(p1.clone(), config.clone(), level, inbox));
+    }
+
+    let stream = stream.aggregate_continuations();
+    rt::spawn(ws_running(
+        state.clone(),
+        Arc::clone(game_arc),
+        player_id,
+        session,
+        stream,
+    ));
+    Ok(res)
+}
+
 #[get("/connect/{game}/{player}")]
 async fn connect(
     request: HttpRequest,
     state: web::Data<Games>,
     stream: web::Payload,
     path: web::Path<(String, String)>,
+    seeds: web::Data<SeedIssuer>,
 ) -> Result<impl Responder, Error> {
     info!("Connect attempt");
     let (game_id, player_id) = path.into_inner();
@@ -172,6 +257,9 @@ async fn connect(
     drop(lock);
 
     let mut game = game_arc.lock().await;
+    if matches!(*game, Game::BotReady { .. }) {
+        return connect_bot(&request, stream, &state, &game_arc, &mut game, player_id).await;
+    }
     let is_p1;
     if let Game::Ready {
         p1,
@@ -246,7 +334,7 @@ async fn connect(
     });
 
     if matches!(*game, Game::Running { .. }) {
-        game.start().await;
+        game.start(&seeds).await;
         info!("Starting game {}", game.get_id());
     }
 
@@ -272,13 +360,19 @@ async fn board_index(state: web::Data<Leaderboard>) -> impl Responder {
     state.get_leaderboard().await
 }
 
+#[get("/seed")]
+async fn new_seed(seeds: web::Data<SeedIssuer>) -> impl Responder {
+    HttpResponse::Ok().json(seeds.issue())
+}
+
 #[post("/highscore")]
 async fn highscore(
     req: web::Json<HighscoreReq>,
     state: web::Data<Leaderboard>,
     store: web::Data<Store>,
+    seeds: web::Data<SeedIssuer>,
 ) -> impl Responder {
-    state.add_entry(req.into_inner(), &store).await
+    state.add_entry(req.into_inner(), &store, &seeds).await
 }
 
 #[actix_web::main]
@@ -294,6 +388,7 @@ async fn main() -> std::io::Result<()> {
         broadcaster: Broadcaster::create(),
     });
     let store = web::Data::new(store);
+    let seeds = web::Data::new(SeedIssuer::new());
     info!("{state:?}");
     info!("Server starting");
     HttpServer::new(move || {
@@ -307,13 +402,17 @@ async fn main() -> std::io::Result<()> {
             .wrap(Compress::default())
             .service(board_index)
             .service(highscore)
+            .service(new_seed)
             .service(ws_index)
             .service(join)
+            .service(bot_game)
             .service(connect)
             .service(all_games)
             .app_data(state.clone())
             .app_data(games.clone())
             .app_data(store.clone())
+            .app_data(seeds.clone())
+            .app_data(web::JsonConfig::default().limit(REPLAY_LIMIT))
     })
     // .bind(("localhost", 4444))?
     // .bind(("172.21.49.178", 4444))?
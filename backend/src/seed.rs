@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+use tetris_core::{net::SeedToken, tetris::RandomSeed};
+use tokio::sync::Mutex;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Tokens that were not redeemed after this time are no longer accepted
+static TOKEN_LIFETIME: Duration = Duration::from_hours(24);
+
+/// Hands out seeds, so highscores can only be submitted for piece sequences chosen by the
+/// server. Tokens are signed, only the redeemed ones are remembered until they expire, so every
+/// token can be redeemed once.
+pub struct SeedIssuer {
+    key: [u8; 32],
+    redeemed: Mutex<HashMap<String, u64>>,
+    /// Current time in seconds since the unix epoch
+    clock: fn() -> u64,
+}
+
+impl SeedIssuer {
+    pub fn new() -> Self {
+        Self {
+            key: rand::rng().random(),
+            redeemed: Mutex::new(HashMap::new()),
+            clock: now,
+        }
+    }
+
+    fn sign(&self, payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    /// Issues a token for a new seed, for singleplayer games
+    pub fn issue(&self) -> SeedToken {
+        let seed: RandomSeed = rand::rng().random();
+        let token = self.token(&seed);
+        SeedToken { token, seed }
+    }
+
+    /// Issues a token for a seed chosen elsewhere, like the seed of a multiplayer game. Every
+    /// call returns a different token, so each player gets their own.
+    pub fn token(&self, seed: &RandomSeed) -> String {
+        let nonce: [u8; 8] = rand::rng().random();
+        let payload = format!("{}.{}.{}", to_hex(seed), (self.clock)(), to_hex(&nonce));
+        let signature = self.sign(&payload).finalize().into_bytes();
+        format!("{payload}.{}", to_hex(&signature))
+    }
+
+    /// Returns the time the token was issued at, if it was issued for this seed and has not
+    /// expired. Does not check if the token was redeemed already.
+    pub fn verify(&self, token: &str, seed: &RandomSeed) -> Option<u64> {
+        let (payload, signature) = token.rsplit_once('.')?;
+        self.sign(payload)
+            .verify_slice(&from_hex(signature)?)
+            .ok()?;
+        let mut parts = payload.split('.');
+        if parts.next()? != to_hex(seed) {
+            return None;
+        }
+        let issued: u64 = parts.next()?.parse().ok()?;
+        ((self.clock)().saturating_sub(issued) < TOKEN_LIFETIME.as_secs()).then_some(issued)
+    }
+
+    /// Returns if the token is valid for this seed and has not been used yet. The token can't be
+    /// used again afterwards.
+    pub async fn redeem(&self, token: &str, seed: &RandomSeed) -> bool {
+        let Some(issued) = self.verify(token, seed) else {
+            return false;
+        };
+        let mut redeemed = self.redeemed.lock().await;
+        let now = (self.clock)();
+        redeemed.retain(|_, issued| now.saturating_sub(*issued) < TOKEN_LIFETIME.as_secs());
+        redeemed.insert(token.to_owned(), issued).is_none()
+    }
+}
+
+/// Seconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut output, byte| {
+        let _ = write!(output, "{byte:02x}");
+        output
+    })
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SeedIssuer, TOKEN_LIFETIME};
+
+    #[actix_web::test]
+    async fn token_only_valid_for_its_seed() {
+        let issuer = SeedIssuer::new();
+        let given = issuer.issue();
+        let mut other = given.seed;
+        other[0] ^= 1;
+        assert!(issuer.verify(&given.token, &given.seed).is_some());
+        assert!(issuer.verify(&given.token, &other).is_none());
+        assert!(!issuer.redeem(&given.token, &other).await);
+        assert!(issuer.redeem(&given.token, &given.seed).await);
+    }
+
+    #[test]
+    fn tampered_token_rejected() {
+        let issuer = SeedIssuer::new();
+        let given = issuer.issue();
+        let (payload, signature) = given.token.rsplit_once('.').unwrap();
+        let flip = |c: char| if c == '0' { '1' } else { '0' };
+
+        let mut bad_signature = signature.to_owned();
+        let first = bad_signature.remove(0);
+        bad_signature.insert(0, flip(first));
+        let token = format!("{payload}.{bad_signature}");
+        assert!(issuer.verify(&token, &given.seed).is_none());
+
+        // a later issue time would keep the token valid forever
+        let mut parts: Vec<&str> = payload.split('.').collect();
+        parts[1] = "99999999999";
+        let token = format!("{}.{signature}", parts.join("."));
+        assert!(issuer.verify(&token, &given.seed).is_none());
+
+        let other = SeedIssuer::new();
+        assert!(other.verify(&given.token, &given.seed).is_none());
+    }
+
+    #[actix_web::test]
+    async fn token_redeemed_once() {
+        let issuer = SeedIssuer::new();
+        let given = issuer.issue();
+        assert!(issuer.redeem(&given.token, &given.seed).await);
+        assert!(!issuer.redeem(&given.token, &given.seed).await);
+
+        let again = issuer.token(&given.seed);
+        assert_ne!(again, given.token);
+        assert!(issuer.redeem(&again, &given.seed).await);
+    }
+
+    #[actix_web::test]
+    async fn expired_token_rejected() {
+        const ISSUED: u64 = 1_000_000;
+        let mut issuer = SeedIssuer::new();
+        issuer.clock = || ISSUED;
+        let given = issuer.issue();
+        issuer.clock = || ISSUED + TOKEN_LIFETIME.as_secs() - 1;
+        assert!(issuer.verify(&given.token, &given.seed).is_some());
+        issuer.clock = || ISSUED + TOKEN_LIFETIME.as_secs();
+        assert!(issuer.verify(&given.token, &given.seed).is_none());
+        assert!(!issuer.redeem(&given.token, &given.seed).await);
+    }
+}
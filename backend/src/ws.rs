@@ -1,6 +1,6 @@
 use std::{sync::Arc, time::Duration};
 
-use actix::clock::{Instant, interval};
+use actix::clock::{Instant, interval, sleep};
 use actix_web::{rt::pin, web};
 use actix_ws::{AggregatedMessage, AggregatedMessageStream, Session};
 use log::info;
@@ -26,6 +26,29 @@ pub async fn waiting_cancel(session: Session, state: web::Data<Games>, id: &Stri
     let _ = session.close(None).await;
 }
 
+/// Removes a bot game if its player did not connect in time
+pub async fn bot_ready_timeout(state: web::Data<Games>, id: String) {
+    sleep(TIMEOUT).await;
+    let Some(game) = state.games.lock().await.get(&id).cloned() else {
+        return;
+    };
+    if !matches!(*game.lock().await, Game::BotReady { .. }) {
+        return;
+    }
+    // the game lock is only tried, running_cancel takes the game before the map
+    let mut lock = state.games.lock().await;
+    let waiting = lock.get(&id).is_some_and(|current| {
+        Arc::ptr_eq(current, &game)
+            && game
+                .try_lock()
+                .is_ok_and(|game| matches!(*game, Game::BotReady { .. }))
+    });
+    if waiting {
+        info!("Bot game {id} timed out");
+        lock.remove(&id);
+    }
+}
+
 pub async fn ws_waiting(
     state: web::Data<Games>,
     id: String,
@@ -170,22 +170,18 @@ impl DrawingContext {
             };
             image
         };
-        for (y, row) in tetrimino.grid.iter().enumerate() {
-            for (x, mino) in row.iter().enumerate() {
-                if *mino {
-                    let dx = if outside_grid {
-                        (x as f64) * 31. + off_x
-                    } else {
-                        (x + tetrimino.offset_x as usize) as f64 * 31. + off_x
-                    };
-                    let dy = if outside_grid {
-                        (y as f64) * 31. + off_y
-                    } else {
-                        (y + tetrimino.offset_y as usize - 20) as f64 * 31. + off_y
-                    };
-                    let _ = image.draw(ctx, dx, dy);
-                }
-            }
+        for (x, y) in tetrimino.grid.cells() {
+            let dx = if outside_grid {
+                (x as f64) * 31. + off_x
+            } else {
+                (x + tetrimino.offset_x as usize) as f64 * 31. + off_x
+            };
+            let dy = if outside_grid {
+                (y as f64) * 31. + off_y
+            } else {
+                (y + tetrimino.offset_y as usize - 20) as f64 * 31. + off_y
+            };
+            let _ = image.draw(ctx, dx, dy);
         }
     }
 
@@ -210,14 +206,24 @@ impl DrawingContext {
         }
     }
 
-    pub fn draw_level(ctx: &CanvasRenderingContext2d, level: u8, x: f64, y: f64) {
-        let text_width = 100.;
+    /// Draws the level or clock, right aligned to `x`
+    pub fn draw_status(ctx: &CanvasRenderingContext2d, text: &str, x: f64, y: f64) {
+        let text_width = 120.;
         ctx.clear_rect(x - text_width, y, text_width, 30.);
         ctx.set_fill_style_str("#099520");
         ctx.set_text_baseline("top");
         ctx.set_font("25px sans-serif");
         ctx.set_text_align("right");
-        let _ = ctx.fill_text(&format!("Level {level} "), x, y);
+        let _ = ctx.fill_text(text, x, y);
+        ctx.set_text_align("start");
+    }
+
+    pub fn draw_banner(ctx: &CanvasRenderingContext2d, text: &str, x: f64, y: f64) {
+        ctx.set_fill_style_str("#ff0");
+        ctx.set_text_baseline("middle");
+        ctx.set_font("35px sans-serif");
+        ctx.set_text_align("center");
+        let _ = ctx.fill_text(text, x, y);
         ctx.set_text_align("start");
     }
 
@@ -239,7 +245,7 @@ impl DrawingContext {
         ctx.fill_rect(x, y, 11. * 10., 21. * 10.);
         for row in 0..20 {
             for col in 0..10 {
-                let mino = board.buffer[row + 20][col];
+                let mino = board.buffer()[row + 20][col];
                 ctx.set_fill_style_str(&get_base_color(mino).to_css());
                 ctx.fill_rect(
                     (col as f64) * 10. + x + 5.,
@@ -1,7 +1,108 @@
-use tetris_core::tetris::Action;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use tetris_core::tetris::{Action, BOARD_WIDTH};
 use wasm_bindgen::prelude::*;
 
-const DAS_DELAY: u8 = 15;
+/// A button of the game, matching the fields of [`FrameInputs`] plus pausing
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Control {
+    Left,
+    Right,
+    Cw,
+    Ccw,
+    Rotate180,
+    Hold,
+    HardDrop,
+    SoftDrop,
+    Pause,
+}
+
+impl Control {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::Cw => "cw",
+            Self::Ccw => "ccw",
+            Self::Rotate180 => "rotate_180",
+            Self::Hold => "hold",
+            Self::HardDrop => "hard_drop",
+            Self::SoftDrop => "soft_drop",
+            Self::Pause => "pause",
+        }
+    }
+}
+
+/// Player preferences for key bindings and auto-repeat
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct HandlingConfig {
+    /// Maps `KeyboardEvent.key` values to controls
+    pub bindings: BTreeMap<String, Control>,
+    /// Delayed auto shift, frames a direction has to be held before it repeats
+    pub das: u8,
+    /// Auto repeat rate, frames between repeated moves once DAS is charged. 0 shifts to the wall
+    /// instantly.
+    pub arr: u8,
+    /// DAS cut delay, frames auto repeat is held back after a rotation or hold
+    pub dcd: u8,
+    /// How many times faster than gravity a soft drop falls
+    pub soft_drop_factor: u8,
+}
+
+impl Default for HandlingConfig {
+    fn default() -> Self {
+        let bindings = [
+            ("ArrowLeft", Control::Left),
+            ("ArrowRight", Control::Right),
+            ("ArrowUp", Control::Cw),
+            ("ArrowDown", Control::SoftDrop),
+            (" ", Control::HardDrop),
+            ("c", Control::Hold),
+            ("z", Control::Ccw),
+            ("x", Control::Rotate180),
+            ("a", Control::Left),
+            ("d", Control::Right),
+            ("s", Control::SoftDrop),
+            ("j", Control::Ccw),
+            ("l", Control::Cw),
+            ("k", Control::Hold),
+            ("Escape", Control::Pause),
+            ("p", Control::Pause),
+        ];
+        Self {
+            bindings: bindings
+                .into_iter()
+                .map(|(key, control)| (key.to_owned(), control))
+                .collect(),
+            das: 15,
+            arr: 1,
+            dcd: 1,
+            soft_drop_factor: 10,
+        }
+    }
+}
+
+impl HandlingConfig {
+    /// Binds the default pause keys if nothing is bound to pause, for settings stored before
+    /// pausing could be rebound
+    fn bind_pause(&mut self) {
+        if self
+            .bindings
+            .values()
+            .any(|&control| control == Control::Pause)
+        {
+            return;
+        }
+        for (key, control) in Self::default().bindings {
+            if control == Control::Pause {
+                self.bindings.entry(key).or_insert(control);
+            }
+        }
+    }
+}
 
 #[allow(clippy::struct_excessive_bools)]
 #[wasm_bindgen]
@@ -10,6 +111,7 @@ pub struct FrameInputs {
     right: bool,
     cw: bool,
     ccw: bool,
+    rotate_180: bool,
     hold: bool,
     hard_drop: bool,
     soft_drop: bool,
@@ -18,12 +120,13 @@ pub struct FrameInputs {
 #[wasm_bindgen]
 impl FrameInputs {
     #[wasm_bindgen(constructor)]
-    #[allow(clippy::nursery, clippy::pedantic)]
+    #[allow(clippy::nursery, clippy::pedantic, clippy::too_many_arguments)]
     pub fn new(
         left: bool,
         right: bool,
         cw: bool,
         ccw: bool,
+        rotate_180: bool,
         hold: bool,
         hard_drop: bool,
         soft_drop: bool,
@@ -33,6 +136,7 @@ impl FrameInputs {
             right,
             cw,
             ccw,
+            rotate_180,
             hold,
             hard_drop,
             soft_drop,
@@ -40,47 +144,146 @@ impl FrameInputs {
     }
 }
 
+/// Auto repeat state of a held button
+#[derive(Default)]
+struct Repeat {
+    /// Frames the button has been held, cycles once DAS is charged
+    frames: u16,
+    /// If the initial press has been sent to the game
+    tapped: bool,
+}
+
 pub struct InputManager {
-    left_frames: u8,
-    right_frames: u8,
-    cw: u8,
-    ccw: u8,
+    pub handling: HandlingConfig,
+    left: Repeat,
+    right: Repeat,
+    cw: Repeat,
+    ccw: Repeat,
+    rotate_180: Repeat,
+    /// If left was pressed after right, so it wins while both are held
+    left_priority: bool,
+    /// Frames left until auto repeat resumes after a rotation or hold
+    cut_frames: u8,
 }
 
 macro_rules! input {
-    ($input:expr, $counter:expr, $actions:ident, $action:ident) => {
-        if $input {
-            $counter += 1;
-            if $counter == 1 || $counter > DAS_DELAY {
-                $actions.push(Action::$action);
-            }
-        } else {
-            $counter = 0;
+    ($self:ident, $input:expr, $repeat:ident, $active:expr, $cut:expr, $actions:ident, $action:ident) => {
+        let moves = Self::charge(&$self.handling, $input, &mut $self.$repeat, $active, $cut);
+        $actions.extend(std::iter::repeat_n(Action::$action, moves));
+    };
+}
+
+macro_rules! rotate {
+    ($self:ident, $input:expr, $repeat:ident, $active:expr, $actions:ident, $action:ident) => {
+        if Self::tap($input, &mut $self.$repeat, $active) {
+            $actions.push(Action::$action);
         }
     };
 }
 
 impl InputManager {
-    pub const fn new() -> Self {
+    pub fn new() -> Self {
         Self {
-            left_frames: 0,
-            right_frames: 0,
-            cw: 0,
-            ccw: 0,
+            handling: HandlingConfig::default(),
+            left: Repeat::default(),
+            right: Repeat::default(),
+            cw: Repeat::default(),
+            ccw: Repeat::default(),
+            rotate_180: Repeat::default(),
+            left_priority: false,
+            cut_frames: 0,
+        }
+    }
+
+    /// Replaces the handling settings
+    pub fn set_handling(&mut self, mut handling: HandlingConfig) {
+        handling.bind_pause();
+        self.handling = handling;
+    }
+
+    /// Returns the control bound to a key
+    pub fn control(&self, key: &str) -> Option<Control> {
+        self.handling.bindings.get(key).copied()
+    }
+
+    /// Advances a held input. Returns how often the action should be emitted this frame. While
+    /// not `active` the charge is kept but nothing is emitted, `cut` holds back auto repeat.
+    fn charge(
+        handling: &HandlingConfig,
+        held: bool,
+        repeat: &mut Repeat,
+        active: bool,
+        cut: bool,
+    ) -> usize {
+        if !held {
+            *repeat = Repeat::default();
+            return 0;
+        }
+        let das = u16::from(handling.das);
+        let arr = u16::from(handling.arr);
+        repeat.frames += 1;
+        if !active {
+            repeat.frames = repeat.frames.min(das.max(1));
+            return 0;
+        }
+        let first = usize::from(!repeat.tapped);
+        repeat.tapped = true;
+        if repeat.frames <= das {
+            return first;
+        }
+        if cut {
+            repeat.frames = das;
+            return first;
+        }
+        if arr == 0 {
+            repeat.frames = das + 1;
+            return BOARD_WIDTH - 1;
+        }
+        if repeat.frames == das + 1 || repeat.frames > das + arr {
+            repeat.frames = das + 1;
+            return 1;
         }
+        0
     }
 
+    /// Advances a held input that does not repeat. Returns if the action should be emitted this
+    /// frame, a press while not `active` is sent once the game accepts input again.
+    fn tap(held: bool, repeat: &mut Repeat, active: bool) -> bool {
+        if !held {
+            *repeat = Repeat::default();
+            return false;
+        }
+        if !active || repeat.tapped {
+            return false;
+        }
+        repeat.tapped = true;
+        true
+    }
+
+    /// Turns the held buttons into actions. `active` is false while the game is not accepting
+    /// input, see [`tetris_core::tetris::Game::accepts_input`].
     pub fn update(
         &mut self,
         inputs: &FrameInputs,
         move_left: bool,
         move_right: bool,
+        active: bool,
     ) -> Vec<Action> {
         let mut actions = vec![];
+        let cut = self.cut_frames > 0;
+        if active {
+            self.cut_frames = self.cut_frames.saturating_sub(1);
+        }
 
-        input!(inputs.left, self.left_frames, actions, Left);
-
-        input!(inputs.right, self.right_frames, actions, Right);
+        if inputs.left && self.left.frames == 0 {
+            self.left_priority = true;
+        } else if inputs.right && self.right.frames == 0 {
+            self.left_priority = false;
+        }
+        let left_active = active && (!inputs.right || self.left_priority);
+        let right_active = active && (!inputs.left || !self.left_priority);
+        input!(self, inputs.left, left, left_active, cut, actions, Left);
+        input!(self, inputs.right, right, right_active, cut, actions, Right);
 
         if inputs.hold {
             actions.push(Action::Hold);
@@ -90,8 +293,24 @@ impl InputManager {
         } else if inputs.soft_drop {
             actions.push(Action::SoftDrop);
         }
-        input!(inputs.cw, self.cw, actions, Cw);
-        input!(inputs.ccw, self.ccw, actions, Ccw);
+        rotate!(self, inputs.cw, cw, active, actions, Cw);
+        rotate!(self, inputs.ccw, ccw, active, actions, Ccw);
+        rotate!(
+            self,
+            inputs.rotate_180,
+            rotate_180,
+            active,
+            actions,
+            Rotate180
+        );
+        if actions.iter().any(|action| {
+            matches!(
+                action,
+                Action::Cw | Action::Ccw | Action::Rotate180 | Action::Hold
+            )
+        }) {
+            self.cut_frames = self.handling.dcd;
+        }
         if move_left && !inputs.left {
             actions.push(Action::Left);
         }
@@ -101,3 +320,128 @@ impl InputManager {
         actions
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn held(left: bool, right: bool) -> FrameInputs {
+        FrameInputs::new(left, right, false, false, false, false, false, false)
+    }
+
+    fn count(actions: &[Action], action: Action) -> usize {
+        actions.iter().filter(|&&a| a == action).count()
+    }
+
+    #[test]
+    fn das_repeats_after_delay() {
+        let mut manager = InputManager::new();
+        manager.handling.arr = 2;
+        let moves: Vec<usize> = (0..20)
+            .map(|_| {
+                count(
+                    &manager.update(&held(true, false), false, false, true),
+                    Action::Left,
+                )
+            })
+            .collect();
+        assert_eq!(moves[0], 1);
+        assert!(moves[1..15].iter().all(|&m| m == 0));
+        assert_eq!(&moves[15..20], &[1, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn instant_shift() {
+        let mut manager = InputManager::new();
+        manager.handling.arr = 0;
+        for _ in 0..15 {
+            manager.update(&held(false, true), false, false, true);
+        }
+        let actions = manager.update(&held(false, true), false, false, true);
+        assert_eq!(count(&actions, Action::Right), BOARD_WIDTH - 1);
+    }
+
+    #[test]
+    fn last_pressed_direction_wins() {
+        let mut manager = InputManager::new();
+        manager.update(&held(true, false), false, false, true);
+        let actions = manager.update(&held(true, true), false, false, true);
+        assert_eq!(actions, vec![Action::Right]);
+        for _ in 0..20 {
+            let actions = manager.update(&held(true, true), false, false, true);
+            assert_eq!(count(&actions, Action::Left), 0);
+        }
+        // left was charged the whole time and takes over at once
+        let actions = manager.update(&held(true, false), false, false, true);
+        assert_eq!(actions, vec![Action::Left]);
+    }
+
+    #[test]
+    fn charge_kept_through_entry_delay() {
+        let mut manager = InputManager::new();
+        for _ in 0..20 {
+            let actions = manager.update(&held(true, false), false, false, false);
+            assert!(actions.is_empty());
+        }
+        for _ in 0..3 {
+            let actions = manager.update(&held(true, false), false, false, true);
+            assert_eq!(actions, vec![Action::Left]);
+        }
+    }
+
+    #[test]
+    fn rotation_cuts_das() {
+        let mut manager = InputManager::new();
+        manager.handling.dcd = 2;
+        for _ in 0..20 {
+            manager.update(&held(true, false), false, false, true);
+        }
+        let rotate = FrameInputs::new(true, false, true, false, false, false, false, false);
+        let actions = manager.update(&rotate, false, false, true);
+        assert_eq!(actions, vec![Action::Left, Action::Cw]);
+        for expected in [0, 0, 1, 1] {
+            let actions = manager.update(&held(true, false), false, false, true);
+            assert_eq!(count(&actions, Action::Left), expected);
+        }
+    }
+
+    #[test]
+    fn held_rotation_does_not_repeat() {
+        let mut manager = InputManager::new();
+        manager.handling.arr = 0;
+        let rotate = FrameInputs::new(false, false, true, false, false, false, false, false);
+        let rotations: usize = (0..30)
+            .map(|_| count(&manager.update(&rotate, false, false, true), Action::Cw))
+            .sum();
+        assert_eq!(rotations, 1);
+        // auto repeat of a direction is not cut by the held rotation
+        let both = FrameInputs::new(true, false, true, false, false, false, false, false);
+        let moves: usize = (0..20)
+            .map(|_| count(&manager.update(&both, false, false, true), Action::Left))
+            .sum();
+        assert!(moves > 1);
+    }
+
+    #[test]
+    fn old_settings_get_pause_keys() {
+        let mut manager = InputManager::new();
+        let mut handling = HandlingConfig::default();
+        handling
+            .bindings
+            .retain(|_, control| *control != Control::Pause);
+        handling.bindings.insert("p".to_owned(), Control::Hold);
+        manager.set_handling(handling);
+        assert_eq!(manager.control("Escape"), Some(Control::Pause));
+        assert_eq!(manager.control("p"), Some(Control::Hold));
+    }
+
+    #[test]
+    fn soft_drop_held_across_pieces() {
+        let mut manager = InputManager::new();
+        let soft_drop = FrameInputs::new(false, false, false, false, false, false, false, true);
+        for active in [true, false, false, true] {
+            let actions = manager.update(&soft_drop, false, false, active);
+            assert_eq!(actions, vec![Action::SoftDrop]);
+        }
+    }
+}
@@ -11,21 +11,21 @@ use serde::Serialize;
 use std::{cell::RefCell, rc::Rc};
 #[cfg(feature = "export")]
 use tetris_core::tetris::Action;
-use wasm_bindgen_futures::spawn_local;
+use wasm_bindgen_futures::{JsFuture, spawn_local};
 
 use crate::{
     codec::CborCodec,
     draw::DrawingContext,
-    input::{FrameInputs, InputManager},
+    input::{FrameInputs, HandlingConfig, InputManager},
     tetris_confirm, tetris_prompt,
 };
 use js_sys::Function;
 use tetris_core::{
-    net::{HighscoreReq, Message},
-    tetris::{Board, Event, Game, GameConfig, GameSettings, Mino, Phase, Tetrimino},
+    net::{HighscoreReq, Message, SeedToken},
+    tetris::{Board, Event, Game, GameConfig, GameSettings, Goal, Mino, Phase, Replay, Tetrimino},
 };
 use wasm_bindgen::prelude::*;
-use web_sys::{CanvasRenderingContext2d, Headers, RequestInit, window};
+use web_sys::{CanvasRenderingContext2d, Headers, RequestInit, Response, window};
 use ws_stream_wasm::{WsMeta, WsStreamIo};
 
 type MessageCodec = CborCodec<Message, Message>;
@@ -35,6 +35,7 @@ type TetrisSession = SplitSink<TetrisFrames, Message>;
 type TetrisStream = SplitStream<TetrisFrames>;
 
 const SHARE_COOLDOWN: u8 = 15;
+const BANNER_FRAMES: u8 = 90;
 const EMPTY_BOARD: Board = Board::new();
 
 #[cfg(feature = "export")]
@@ -62,7 +63,12 @@ pub struct Instance {
     opponent_board: Rc<RefCell<Option<Board>>>,
     messages: Rc<RefCell<Vec<(String, String)>>>,
     share_cooldown: u8,
+    banner_frames: u8,
     is_multiplayer: bool,
+    /// Inputs of the current game
+    replay: Rc<RefCell<Option<Replay>>>,
+    /// Token for the seed of the current game
+    seed_token: Rc<RefCell<Option<String>>>,
     #[cfg(feature = "export")]
     data: Vec<ExportFrame>,
 }
@@ -85,8 +91,11 @@ impl Instance {
             backend_url,
             opponent_board: Rc::new(RefCell::new(None)),
             share_cooldown: SHARE_COOLDOWN,
+            banner_frames: 0,
             messages: Rc::new(RefCell::new(Vec::with_capacity(1))),
             is_multiplayer: false,
+            replay: Rc::new(RefCell::new(None)),
+            seed_token: Rc::new(RefCell::new(None)),
             #[cfg(feature = "export")]
             data: Vec::new(),
         }
@@ -98,6 +107,38 @@ impl Instance {
         serde_cbor::ser::to_vec_packed(&self.data).unwrap_or_default()
     }
 
+    /// Loads handling settings from JSON, returns if they were valid
+    #[wasm_bindgen]
+    pub fn load_handling(&mut self, json: &str) -> bool {
+        serde_json_wasm::from_str::<HandlingConfig>(json)
+            .map(|handling| self.input_manager.set_handling(handling))
+            .is_ok()
+    }
+
+    /// Current handling settings as JSON, so they can be stored by the page
+    #[wasm_bindgen]
+    pub fn save_handling(&self) -> String {
+        serde_json_wasm::to_string(&self.input_manager.handling).unwrap_or_default()
+    }
+
+    /// Name of the control bound to a key, `undefined` if it isn't bound
+    #[wasm_bindgen]
+    pub fn key_control(&self, key: &str) -> Option<String> {
+        self.input_manager
+            .control(key)
+            .map(|control| control.name().to_owned())
+    }
+
+    /// Packed replay of the last game, empty if there is none
+    #[wasm_bindgen]
+    pub fn get_replay(&self) -> Vec<u8> {
+        self.replay
+            .borrow()
+            .as_ref()
+            .map(Replay::to_bytes)
+            .unwrap_or_default()
+    }
+
     #[wasm_bindgen]
     pub fn draw(&self) {
         const BOARD_X: f64 = 160.;
@@ -108,9 +149,21 @@ impl Instance {
         };
         self.drawing_context
             .draw_board(&self.context, BOARD_X, BOARD_Y);
+        if game.paused {
+            self.drawing_context
+                .draw_hold(&self.context, None, 20., BOARD_Y);
+            self.drawing_context.draw_queue(
+                &self.context,
+                std::iter::empty(),
+                BOARD_X + 350.,
+                BOARD_Y,
+            );
+            DrawingContext::draw_banner(&self.context, "Paused", BOARD_X + 160., BOARD_Y + 300.);
+            return;
+        }
         self.drawing_context.draw_field(
             &self.context,
-            &game.board.buffer,
+            game.board.buffer(),
             BOARD_X + 5.,
             BOARD_Y + 5.,
         );
@@ -157,7 +210,22 @@ impl Instance {
                 BOARD_Y + 430.,
             );
         }
-        DrawingContext::draw_level(&self.context, game.level, BOARD_X + 320., 20.);
+        // timed modes stay on one level, show the clock instead
+        let status = match game.ruleset.goal {
+            Goal::Endless | Goal::Level(_) => format!("Level {} ", game.level),
+            Goal::Lines(_) | Goal::Dig(_) => format_time(game.frame),
+            Goal::Time(limit) => format_time(limit.saturating_sub(game.frame)),
+        };
+        DrawingContext::draw_status(&self.context, &status, BOARD_X + 320., 20.);
+
+        if self.banner_frames > 0 {
+            DrawingContext::draw_banner(
+                &self.context,
+                "Perfect Clear!",
+                BOARD_X + 160.,
+                BOARD_Y + 200.,
+            );
+        }
 
         DrawingContext::draw_messages(
             &self.context,
@@ -169,9 +237,16 @@ impl Instance {
 
     /// Should be called exaclty 60 times a second
     #[wasm_bindgen]
-    #[allow(clippy::pedantic)]
+    // TODO: RefCell is held across await point, see TODO.md
+    #[allow(clippy::pedantic, clippy::await_holding_refcell_ref)]
     pub async fn update(&mut self, inputs: FrameInputs, move_left: bool, move_right: bool) -> bool {
-        let frame_actions = self.input_manager.update(&inputs, move_left, move_right);
+        if self.game.borrow().as_ref().is_some_and(|game| game.paused) {
+            return true;
+        }
+        let active = self.game.borrow().as_ref().is_some_and(Game::accepts_input);
+        let frame_actions = self
+            .input_manager
+            .update(&inputs, move_left, move_right, active);
 
         let mut borrow = self.game.borrow_mut();
         let Some(ref mut game) = *borrow else {
@@ -191,10 +266,14 @@ impl Instance {
                 inputs: frame_actions.clone(),
             });
         }
+        if let Some(ref mut replay) = *self.replay.borrow_mut() {
+            replay.record(&frame_actions);
+        }
         game.user_actions(frame_actions);
         let events = game.events.clone();
         drop(borrow);
 
+        self.banner_frames = self.banner_frames.saturating_sub(1);
         self.share_cooldown -= 1;
         if self.share_cooldown == 0 {
             self.share_cooldown = SHARE_COOLDOWN;
@@ -208,17 +287,52 @@ impl Instance {
         }
         for event in &events {
             match event {
-                Event::Gameover => {
+                Event::Gameover | Event::Finished { .. } | Event::TimeUp { .. } => {
                     let mut game = self.game.borrow_mut();
                     if let Some(ref mut game) = *game
                         && !game.settings.easy
+                        && let Some(ref replay) = *self.replay.borrow()
                     {
+                        // timed modes are ranked by the frames it took to finish, level caps by score
+                        let (question, result) = match event {
+                            Event::Finished { .. }
+                                if matches!(game.ruleset.goal, Goal::Level(_)) =>
+                            {
+                                (
+                                    format!(
+                                        "Reached level {}! You scored {}, do you want to share your score?",
+                                        game.level, game.score
+                                    ),
+                                    game.score,
+                                )
+                            }
+                            Event::Finished { frames } => (
+                                format!(
+                                    "Finished in {} with {} pieces!, do you want to share your time?",
+                                    format_time(*frames),
+                                    game.pieces
+                                ),
+                                *frames,
+                            ),
+                            Event::TimeUp { score } => (
+                                format!(
+                                    "Time's up! You scored {score}, do you want to share your score?"
+                                ),
+                                *score,
+                            ),
+                            _ => (
+                                String::from("You lost!, do you want to share your score?"),
+                                game.score,
+                            ),
+                        };
                         gameover(
                             &self.backend_url,
                             &self.auth_func,
-                            game.score,
+                            &question,
+                            result,
                             self.is_multiplayer,
-                            game.settings,
+                            replay.clone(),
+                            self.seed_token.borrow().clone(),
                         );
                     }
                     *game = None;
@@ -230,17 +344,13 @@ impl Instance {
                     }
                     return false;
                 }
-                Event::Completion(lines) => {
+                Event::Completion(_) => {}
+                Event::PerfectClear => {
+                    self.banner_frames = BANNER_FRAMES;
+                }
+                Event::Attack(lines) => {
                     if let Some(ref mut session) = *self.session.borrow_mut() {
-                        let lines = match lines {
-                            2 => 1,
-                            3 => 2,
-                            4 => 4,
-                            _ => 0,
-                        };
-                        if lines > 0 {
-                            let _ = session.send(Message::LineSend(lines)).await;
-                        }
+                        let _ = session.send(Message::LineSend(*lines)).await;
                     }
                 }
             }
@@ -268,26 +378,59 @@ impl Instance {
             self.game.clone(),
             Rc::clone(&self.opponent_board),
             Rc::clone(&self.messages),
+            Rc::clone(&self.replay),
+            Rc::clone(&self.seed_token),
+            self.input_manager.handling.soft_drop_factor,
         ));
 
         self.session = session;
         self.is_multiplayer = true;
+        *self.seed_token.borrow_mut() = None;
     }
 
+    /// Starts a game with a seed from the server. If the server can't be reached, a local seed is
+    /// used and the score can't be submitted.
     #[wasm_bindgen]
-    pub fn start_singleplayer(&mut self, settings: GameSettings) -> bool {
-        let config = GameConfig::default_seed(settings);
+    pub async fn start_singleplayer(&mut self, settings: GameSettings) -> bool {
+        let seed = fetch_seed(&self.backend_url).await;
+        let mut config = seed.as_ref().map_or_else(
+            || GameConfig::default_seed(settings),
+            |seed| GameConfig::with_seed(settings, seed.seed),
+        );
+        config.soft_drop_factor = self.input_manager.handling.soft_drop_factor;
+        let replay = Replay::new(config);
         let Ok(mut game) = self.game.try_borrow_mut() else {
             return false;
         };
         if game.is_some() {
             return false;
         }
-        game.get_or_insert(Game::new(config));
+        game.get_or_insert(Game::new(replay.config.clone()));
         self.is_multiplayer = false;
+        *self.replay.borrow_mut() = Some(replay);
+        *self.seed_token.borrow_mut() = seed.map(|seed| seed.token);
         true
     }
 
+    /// Pauses or resumes the game. Multiplayer games can't be paused. Returns if the game was
+    /// paused or resumed.
+    #[wasm_bindgen]
+    pub fn toggle_pause(&self) -> bool {
+        if self.is_multiplayer {
+            return false;
+        }
+        let mut game = self.game.borrow_mut();
+        let Some(ref mut game) = *game else {
+            return false;
+        };
+        if game.paused {
+            game.resume();
+            true
+        } else {
+            game.pause()
+        }
+    }
+
     #[wasm_bindgen]
     pub fn goodbye(&self) {
         let mut game = self.game.borrow_mut();
@@ -302,12 +445,16 @@ impl Instance {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn conn_loop_static(
     _meta: WsMeta,
     mut stream: TetrisStream,
     game: Rc<RefCell<Option<Game>>>,
     opponent_board: Rc<RefCell<Option<Board>>>,
     messages: Rc<RefCell<Vec<(String, String)>>>,
+    replay: Rc<RefCell<Option<Replay>>>,
+    seed_token: Rc<RefCell<Option<String>>>,
+    soft_drop_factor: u8,
 ) {
     while let Some(msg) = stream.next().await {
         let Ok(msg) = msg else {
@@ -317,12 +464,19 @@ async fn conn_loop_static(
             Message::LineSend(lines) => {
                 if let Some(ref mut game) = *game.borrow_mut() {
                     game.accumulate_garbage(lines);
+                    if let Some(ref mut replay) = *replay.borrow_mut() {
+                        replay.record_garbage(lines);
+                    }
                 }
             }
-            Message::Start(settings) => {
+            Message::Start(mut config, token) => {
                 let try_borrow_mut = game.try_borrow_mut();
                 if let Ok(mut game) = try_borrow_mut {
-                    *game = Some(Game::new(settings));
+                    config.soft_drop_factor = soft_drop_factor;
+                    let new_replay = Replay::new(config);
+                    *game = Some(Game::new(new_replay.config.clone()));
+                    *replay.borrow_mut() = Some(new_replay);
+                    *seed_token.borrow_mut() = Some(token);
                 }
             }
             Message::Gameover | Message::Disconnect => {
@@ -342,12 +496,14 @@ async fn conn_loop_static(
 fn gameover(
     backend_url: &str,
     auth_func: &Function,
+    question: &str,
     score: u32,
     is_multiplayer: bool,
-    settings: GameSettings,
+    replay: Replay,
+    seed_token: Option<String>,
 ) {
     let window = window().unwrap();
-    if !tetris_confirm("You lost!, do you want to share your score?") {
+    if !tetris_confirm(question) {
         return;
     }
     let Some(name) = tetris_prompt("Enter your name for the leaderboard:") else {
@@ -372,11 +528,33 @@ fn gameover(
         auth: token,
         name,
         score,
-        settings,
         was_multiplayer: is_multiplayer,
+        replay,
+        seed_token,
     };
     options.set_body(&JsValue::from_str(
         &serde_json_wasm::to_string(&req).unwrap(),
     ));
     let _ = window.fetch_with_str_and_init(&format!("{backend_url}/highscore"), &options);
 }
+
+/// Formats a frame count as minutes, seconds and milliseconds
+fn format_time(frames: u32) -> String {
+    let millis = u64::from(frames) * 1000 / 60;
+    format!(
+        "{}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+async fn fetch_seed(backend_url: &str) -> Option<SeedToken> {
+    let response = window()?.fetch_with_str(&format!("{backend_url}/seed"));
+    let response: Response = JsFuture::from(response).await.ok()?.dyn_into().ok()?;
+    let text = JsFuture::from(response.text().ok()?)
+        .await
+        .ok()?
+        .as_string()?;
+    serde_json_wasm::from_str(&text).ok()
+}
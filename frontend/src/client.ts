@@ -8,6 +8,16 @@ export function initAlpine(connect: (game: string) => void, runSinglePlayer: (se
       connect(id);
     },
 
+    async playBot(settings: { jupiter: boolean; easy: boolean; nes: boolean; random: boolean; difficulty: string }) {
+      const url = new URL(window.backendUrl + "/bot-game/" + settings.difficulty);
+      url.searchParams.set("jupiter", String(settings.jupiter));
+      url.searchParams.set("easy", String(settings.easy));
+      url.searchParams.set("nes", String(settings.nes));
+      url.searchParams.set("random", String(settings.random));
+      const id = await (await fetch(url)).json();
+      connect(id);
+    },
+
     connect,
 
     runSinglePlayer,
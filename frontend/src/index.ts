@@ -1,5 +1,6 @@
 import init, {
   FrameInputs,
+  GameMode,
   GameSettings,
   init_panic_hook,
   Instance,
@@ -35,23 +36,16 @@ let running = false;
 
 var then = window.performance.now();
 
-const keyMap = {
-  ArrowLeft: "left",
-  ArrowRight: "right",
-  ArrowUp: "cw",
-  ArrowDown: "soft_drop",
-  " ": "hard_drop",
-  c: "hold",
-  z: "ccw",
-  a: "left",
-  d: "right",
-  s: "soft_drop",
-  j: "ccw",
-  l: "cw",
-  k: "hold",
-} as const;
-type KeyAction = keyof typeof keyMap;
-type Action = (typeof keyMap)[KeyAction];
+type Action =
+  | "left"
+  | "right"
+  | "cw"
+  | "ccw"
+  | "rotate_180"
+  | "hold"
+  | "hard_drop"
+  | "soft_drop";
+type Control = Action | "pause";
 const pressedKeys: Set<Action> = new Set();
 
 const controls: Action[] = [
@@ -59,6 +53,7 @@ const controls: Action[] = [
   "right",
   "cw",
   "ccw",
+  "rotate_180",
   "hold",
   "hard_drop",
   "soft_drop",
@@ -72,6 +67,12 @@ if (!ctx) {
 }
 const game = new Instance(ctx, generateAuthToken, window.backendUrl);
 
+const HANDLING_KEY = "handling";
+const storedHandling = localStorage.getItem(HANDLING_KEY);
+if (storedHandling === null || !game.load_handling(storedHandling)) {
+  localStorage.setItem(HANDLING_KEY, game.save_handling());
+}
+
 const joinGame = async (gameId: string) => {
   if (running) {
     return;
@@ -85,21 +86,27 @@ const joinGame = async (gameId: string) => {
   }
 };
 
-const runSinglePlayer = (settings: Pick<GameSettings, keyof GameSettings>) => {
+type SinglePlayerSettings = Omit<Pick<GameSettings, keyof GameSettings>, "mode"> & {
+  mode: keyof typeof GameMode;
+};
+
+const runSinglePlayer = (settings: SinglePlayerSettings) => {
   if (running) {
     return;
   }
 
   pressedKeys.clear();
-  game.start_singleplayer(
-    new GameSettings(
-      settings.jupiter,
-      settings.easy,
-      settings.nes,
-      settings.random
+  game
+    .start_singleplayer(
+      new GameSettings(
+        settings.jupiter,
+        settings.easy,
+        settings.nes,
+        settings.random,
+        GameMode[settings.mode]
+      )
     )
-  );
-  startGame();
+    .then((started) => started && startGame());
 };
 
 const stopEverything = () => {
@@ -157,6 +164,7 @@ async function update(newtime: number) {
     boolean,
     boolean,
     boolean,
+    boolean,
     boolean
   ];
 
@@ -190,16 +198,20 @@ async function update(newtime: number) {
 }
 
 window.addEventListener("keydown", (e) => {
-  const action = (keyMap as Record<string, Action | undefined>)[e.key];
-  if (action) {
-    pressedKeys.add(action);
+  const control = game.key_control(e.key) as Control | undefined;
+  if (control === "pause") {
+    if (running && game.toggle_pause()) {
+      pressedKeys.clear();
+    }
+  } else if (control) {
+    pressedKeys.add(control);
   }
 });
 
 window.addEventListener("keyup", (e) => {
-  const action = (keyMap as Record<string, Action | undefined>)[e.key];
-  if (action) {
-    pressedKeys.delete(action);
+  const control = game.key_control(e.key) as Control | undefined;
+  if (control && control !== "pause") {
+    pressedKeys.delete(control);
   }
 });
 
@@ -1,10 +1,11 @@
 use serde::{Deserialize, Serialize};
 
-use crate::tetris::{Board, GameConfig, GameSettings};
+use crate::tetris::{Board, GameConfig, RandomSeed, Replay};
 
 #[derive(Serialize, Deserialize)]
 pub enum Message {
-    Start(GameConfig),
+    /// Starts the game, with the token to submit a highscore for its seed
+    Start(GameConfig, String),
     LineSend(u8),
     GameState(Box<Board>),
     Gameover,
@@ -15,7 +16,18 @@ pub enum Message {
 pub struct HighscoreReq {
     pub auth: String,
     pub name: String,
-    pub settings: GameSettings,
     pub was_multiplayer: bool,
     pub score: u32,
+    /// Verified by re-simulating it on the server
+    pub replay: Replay,
+    /// Token received with the seed of the game
+    pub seed_token: Option<String>,
+}
+
+/// Seed for a singleplayer game, issued by the server. Multiplayer games get their token with
+/// [`Message::Start`].
+#[derive(Serialize, Deserialize)]
+pub struct SeedToken {
+    pub token: String,
+    pub seed: RandomSeed,
 }
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Clear, TSpin};
+
+/// Lines sent to the opponent for each kind of clear. Bonuses are added on top of the base value.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttackTable {
+    /// Indexed by lines cleared
+    pub lines: [u8; 5],
+    /// Indexed by lines cleared with a T-spin
+    pub t_spin: [u8; 4],
+    /// Indexed by lines cleared with a T-spin mini
+    pub t_spin_mini: [u8; 3],
+    /// Indexed by combo count, the last entry is used for longer combos
+    pub combo: [u8; 11],
+    pub back_to_back: u8,
+    pub perfect_clear: u8,
+}
+
+impl Default for AttackTable {
+    fn default() -> Self {
+        Self::GUIDELINE
+    }
+}
+
+impl AttackTable {
+    pub const GUIDELINE: Self = Self {
+        lines: [0, 0, 1, 2, 4],
+        t_spin: [0, 2, 4, 6],
+        t_spin_mini: [0, 0, 1],
+        combo: [0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+        back_to_back: 1,
+        perfect_clear: 10,
+    };
+
+    /// Lines sent for a clear, before cancelling incoming garbage
+    pub fn attack(&self, clear: &Clear, perfect_clear: bool) -> u8 {
+        let lines = clear.lines as usize;
+        let mut attack = match clear.t_spin {
+            TSpin::None => self.lines.get(lines),
+            TSpin::Mini => self.t_spin_mini.get(lines),
+            TSpin::Full => self.t_spin.get(lines),
+        }
+        .copied()
+        .unwrap_or_default();
+        if clear.back_to_back {
+            attack = attack.saturating_add(self.back_to_back);
+        }
+        if let Some(combo) = clear.combo {
+            let combo = (combo as usize).min(self.combo.len() - 1);
+            attack = attack.saturating_add(self.combo[combo]);
+        }
+        if perfect_clear {
+            attack = attack.saturating_add(self.perfect_clear);
+        }
+        attack
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AttackTable;
+    use crate::tetris::{Clear, TSpin};
+
+    #[test]
+    fn guideline_attack() {
+        let table = AttackTable::GUIDELINE;
+        let tetris = Clear {
+            lines: 4,
+            t_spin: TSpin::None,
+            combo: Some(0),
+            back_to_back: false,
+        };
+        assert_eq!(table.attack(&tetris, false), 4);
+        assert_eq!(table.attack(&tetris, true), 14);
+
+        let tsd = Clear {
+            lines: 2,
+            t_spin: TSpin::Full,
+            combo: Some(3),
+            back_to_back: true,
+        };
+        assert_eq!(table.attack(&tsd, false), 4 + 1 + 2);
+
+        let single = Clear {
+            lines: 1,
+            t_spin: TSpin::None,
+            combo: Some(20),
+            back_to_back: false,
+        };
+        assert_eq!(table.attack(&single, false), 5);
+    }
+}
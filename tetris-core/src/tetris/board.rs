@@ -4,6 +4,8 @@ use serde::{
     ser::SerializeTuple,
 };
 
+use super::{RotationSystem, Srs};
+
 pub const BOARD_WIDTH: usize = 10;
 pub const BOARD_HEIGHT: usize = 40;
 
@@ -23,7 +25,7 @@ pub enum Mino {
 }
 
 #[repr(u8)]
-#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum Rotation {
     #[default]
     Zero,
@@ -57,6 +59,7 @@ impl Rotation {
         match direction {
             Direction::Cw => self.cw(),
             Direction::Ccw => self.ccw(),
+            Direction::Half => self.cw().cw(),
         }
     }
 }
@@ -65,11 +68,36 @@ impl Rotation {
 pub enum Direction {
     Cw,
     Ccw,
+    /// 180 degrees
+    Half,
 }
 
+/// Playfield with a row at the top and column at the left at 0. Besides the minos for drawing
+/// every row keeps a bitmask of its filled cells, bit `x` is column `x`. Collisions and line
+/// clears only look at the masks.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Board {
-    pub buffer: [[Mino; BOARD_WIDTH]; BOARD_HEIGHT],
+    buffer: [[Mino; BOARD_WIDTH]; BOARD_HEIGHT],
+    rows: [u16; BOARD_HEIGHT],
+}
+
+const FULL_ROW: u16 = (1 << BOARD_WIDTH) - 1;
+
+/// Moves a row of a [`Shape`] to column `x`. Returns `None` if a cell would leave the board.
+fn shift_row(row: u8, x: i8) -> Option<u16> {
+    if row == 0 {
+        return Some(0);
+    }
+    if !(-8..=16).contains(&x) {
+        return None;
+    }
+    // leave room for the cells left of the board in the lowest 8 bits
+    let wide = u32::from(row) << 8;
+    let moved = if x >= 0 { wide << x } else { wide >> -x };
+    if moved & !(u32::from(FULL_ROW) << 8) != 0 {
+        return None;
+    }
+    Some((moved >> 8) as u16)
 }
 
 impl Serialize for Board {
@@ -130,7 +158,14 @@ impl<'de> Deserialize<'de> for Board {
                     }
                 }
 
-                Ok(Board { buffer: board })
+                let mut rows = [0; BOARD_HEIGHT];
+                for (mask, row) in rows.iter_mut().zip(board) {
+                    *mask = Board::row_mask(&row);
+                }
+                Ok(Board {
+                    buffer: board,
+                    rows,
+                })
             }
         }
         deserializer.deserialize_tuple(BOARD_WIDTH * BOARD_HEIGHT, BoardVisitor)
@@ -147,6 +182,32 @@ impl Board {
     pub const fn new() -> Self {
         Self {
             buffer: [[Mino::Empty; BOARD_WIDTH]; BOARD_HEIGHT],
+            rows: [0; BOARD_HEIGHT],
+        }
+    }
+
+    fn row_mask(row: &[Mino; BOARD_WIDTH]) -> u16 {
+        row.iter()
+            .enumerate()
+            .filter(|(_, mino)| **mino != Mino::Empty)
+            .fold(0, |mask, (x, _)| mask | 1 << x)
+    }
+
+    pub const fn buffer(&self) -> &[[Mino; BOARD_WIDTH]; BOARD_HEIGHT] {
+        &self.buffer
+    }
+
+    /// Filled cells of a row, bit `x` is column `x`
+    pub const fn row(&self, y: usize) -> u16 {
+        self.rows[y]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, mino: Mino) {
+        self.buffer[y][x] = mino;
+        if mino == Mino::Empty {
+            self.rows[y] &= !(1 << x);
+        } else {
+            self.rows[y] |= 1 << x;
         }
     }
 
@@ -154,51 +215,34 @@ impl Board {
         if tetrimino.offset_y < 0 {
             return;
         }
-        for (y, row) in tetrimino.grid.iter().enumerate() {
-            for (x, mino) in row.iter().enumerate() {
-                if *mino {
-                    self.buffer[y + tetrimino.offset_y as usize][x + tetrimino.offset_x as usize] =
-                        tetrimino.kind;
-                }
-            }
-        }
-    }
-
-    fn check_collisions(&self, tetrimino: &Tetrimino) -> bool {
-        for (y, row) in tetrimino.grid.iter().enumerate() {
-            for (x, mino) in row.iter().enumerate() {
-                if *mino
-                    && self.buffer[y + tetrimino.offset_y as usize][x + tetrimino.offset_x as usize]
-                        != Mino::Empty
-                {
-                    return true;
-                }
+        for (x, y) in tetrimino.grid.cells() {
+            let x = x as i8 + tetrimino.offset_x;
+            let y = y as i8 + tetrimino.offset_y;
+            if (0..BOARD_WIDTH as i8).contains(&x) && (0..BOARD_HEIGHT as i8).contains(&y) {
+                self.set(x as usize, y as usize, tetrimino.kind);
             }
         }
-        false
     }
 
-    fn check_oob(tetrimino: &Tetrimino) -> bool {
-        for (y, row) in tetrimino.grid.iter().enumerate() {
-            for (x, mino) in row.iter().enumerate() {
-                if !*mino {
-                    continue;
-                }
-                let x = x as i8 + tetrimino.offset_x;
-                if !(0..10).contains(&x) {
-                    return true;
-                }
-                let y = y as i8 + tetrimino.offset_y;
-                if !(0..40).contains(&y) {
-                    return true;
-                }
-            }
+    /// Returns if the cell is filled. Cells outside of the board count as filled.
+    pub fn is_occupied(&self, x: i8, y: i8) -> bool {
+        if !(0..BOARD_WIDTH as i8).contains(&x) || !(0..BOARD_HEIGHT as i8).contains(&y) {
+            return true;
         }
-        false
+        self.rows[y as usize] & 1 << x != 0
     }
 
     pub fn can_place(&self, tetrimino: &Tetrimino) -> bool {
-        !Self::check_oob(tetrimino) && !self.check_collisions(tetrimino)
+        tetrimino.grid.rows().iter().zip(0..).all(|(&row, y)| {
+            if row == 0 {
+                return true;
+            }
+            let y = i16::from(tetrimino.offset_y) + y;
+            let Some(mask) = shift_row(row, tetrimino.offset_x) else {
+                return false;
+            };
+            (0..BOARD_HEIGHT as i16).contains(&y) && self.rows[y as usize] & mask == 0
+        })
     }
 
     pub fn drop(&self, tetrimino: &mut Tetrimino) {
@@ -208,26 +252,24 @@ impl Board {
         tetrimino.offset_y -= 1;
     }
 
-    /// Rotates the tetrimino with wall-kicks. Returns if the rotation was successful.
+    /// Rotates the tetrimino with the kicks of the rotation system. Returns the index of the kick
+    /// that was used, or `None` if the rotation failed.
     pub fn rotate(
         &self,
         tetrimino: &mut Tetrimino,
+        system: &dyn RotationSystem,
         can_floor_kick: bool,
         direction: Direction,
-    ) -> bool {
+    ) -> Option<usize> {
         let to = tetrimino.rotation.rotate(direction);
-        let offsets = match tetrimino.kind {
-            Mino::J | Mino::L | Mino::S | Mino::Z | Mino::T => {
-                Self::get_three_offsets(tetrimino.rotation, to)
-            }
-            Mino::I => Self::get_i_offsets(tetrimino.rotation, to),
-            _ => return true,
-        };
-
         let mut clone = tetrimino.clone();
-        clone.rotate_grid(direction);
+        clone.grid = system.grid(tetrimino.kind, to);
 
-        for (x, y) in offsets {
+        for (kick, &(x, y)) in system
+            .kicks(tetrimino.kind, tetrimino.rotation, to)
+            .iter()
+            .enumerate()
+        {
             clone.offset_x += x;
             clone.offset_y += y;
             if !self.can_place(&clone) || (!can_floor_kick && y == -2) {
@@ -235,36 +277,12 @@ impl Board {
                 clone.offset_y -= y;
                 continue;
             }
-            tetrimino.rotate_grid(direction);
-            tetrimino.offset_x += x;
-            tetrimino.offset_y += y;
-            tetrimino.rotation = to;
-            return true;
+            clone.rotation = to;
+            *tetrimino = clone;
+            return Some(kick);
         }
 
-        false
-    }
-
-    fn get_three_offsets(from: Rotation, to: Rotation) -> [(i8, i8); 5] {
-        use Rotation as R;
-        match (from, to) {
-            (_, R::Right) => [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
-            (R::Right, _) => [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
-            (_, R::Left) => [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
-            (R::Left, _) => [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
-            _ => unreachable!("Tried to invalid rotation from: {from:?}, to: {to:?}"),
-        }
-    }
-
-    fn get_i_offsets(from: Rotation, to: Rotation) -> [(i8, i8); 5] {
-        use Rotation as R;
-        match (from, to) {
-            (R::Zero, R::Right) | (R::Left, R::Two) => [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
-            (R::Right, R::Zero) | (R::Two, R::Left) => [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
-            (R::Right, R::Two) | (R::Zero, R::Left) => [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
-            (R::Two, R::Right) | (R::Left, R::Zero) => [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
-            _ => unreachable!("Tried invalid rotation from: {from:?}, to: {to:?}"),
-        }
+        None
     }
 
     /// Move along the x-axis. Returns if movement was a success
@@ -296,43 +314,126 @@ impl Board {
 
     pub fn clear_lines(&mut self) -> u8 {
         let mut count = 0;
-        for i in 0..40 {
-            let row = self.buffer[i];
-            if row.iter().all(|x| *x != Mino::Empty) {
+        let mut write = BOARD_HEIGHT;
+        for read in (0..BOARD_HEIGHT).rev() {
+            if self.rows[read] == FULL_ROW {
                 count += 1;
-                for j in (1..=i).rev() {
-                    let copy = self.buffer[j - 1];
-                    self.buffer[j] = copy;
-                }
-                self.buffer[0] = [Mino::Empty; 10];
+                continue;
             }
+            write -= 1;
+            self.buffer[write] = self.buffer[read];
+            self.rows[write] = self.rows[read];
+        }
+        for y in 0..write {
+            self.buffer[y] = [Mino::Empty; BOARD_WIDTH];
+            self.rows[y] = 0;
         }
         count
     }
 
-    /// Returns if would be gameover
+    pub fn has_garbage(&self) -> bool {
+        self.buffer.iter().any(|row| row.contains(&Mino::Garbage))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|row| *row == 0)
+    }
+
+    /// Returns if would be gameover, pushing up more than the whole board always is
     pub fn push_up(&mut self, amount: u8) -> bool {
         let amount = amount as usize;
-        for i in 0..amount {
-            if self.buffer[i].iter().any(|x| *x != Mino::Empty) {
-                return true;
-            }
-        }
-
-        for i in 0..(40 - amount) {
-            for j in 0..10 {
-                self.buffer[i][j] = self.buffer[i + amount][j];
-            }
+        if amount > BOARD_HEIGHT || self.rows[..amount].iter().any(|row| *row != 0) {
+            return true;
         }
+        self.buffer.copy_within(amount.., 0);
+        self.rows.copy_within(amount.., 0);
         false
     }
 
     pub fn add_garbage(&mut self, row: u8, slot: u8) {
         let row = row as usize;
-        for i in 0..10 {
-            self.buffer[row][i] = Mino::Garbage;
-        }
+        self.buffer[row] = [Mino::Garbage; BOARD_WIDTH];
         self.buffer[row][slot as usize] = Mino::Empty;
+        self.rows[row] = FULL_ROW & !(1 << slot);
+    }
+}
+
+/// Cells of a piece in one rotation state, in a square box of `size` rows from the top. Bit `x`
+/// of a row is column `x`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Shape {
+    rows: [u8; 4],
+    size: u8,
+}
+
+impl Shape {
+    /// Parses a piece drawn with `#` for filled cells, one string per row
+    pub const fn parse(rows: &[&str]) -> Self {
+        let mut shape = Self {
+            rows: [0; 4],
+            size: rows.len() as u8,
+        };
+        let mut y = 0;
+        while y < rows.len() {
+            let row = rows[y].as_bytes();
+            let mut x = 0;
+            while x < row.len() {
+                if row[x] == b'#' {
+                    shape.rows[y] |= 1 << x;
+                }
+                x += 1;
+            }
+            y += 1;
+        }
+        shape
+    }
+
+    pub const fn size(self) -> usize {
+        self.size as usize
+    }
+
+    pub fn rows(&self) -> &[u8] {
+        &self.rows[..self.size()]
+    }
+
+    pub const fn is_filled(self, x: usize, y: usize) -> bool {
+        x < self.size() && y < self.size() && self.rows[y] & 1 << x != 0
+    }
+
+    /// Filled cells as `(x, y)`
+    pub fn cells(self) -> impl Iterator<Item = (usize, usize)> {
+        let size = self.size();
+        (0..size)
+            .flat_map(move |y| (0..size).map(move |x| (x, y)))
+            .filter(move |&(x, y)| self.is_filled(x, y))
+    }
+
+    pub const fn rotate(self, direction: Direction) -> Self {
+        match direction {
+            Direction::Cw => self.rotate_cw(),
+            Direction::Ccw => self.rotate_cw().rotate_cw().rotate_cw(),
+            Direction::Half => self.rotate_cw().rotate_cw(),
+        }
+    }
+
+    const fn rotate_cw(self) -> Self {
+        let n = self.size();
+        let mut rotated = Self {
+            rows: [0; 4],
+            size: self.size,
+        };
+        let mut y = 0;
+        while y < n {
+            let mut x = 0;
+            while x < n {
+                if self.is_filled(y, n - 1 - x) {
+                    rotated.rows[y] |= 1 << x;
+                }
+                x += 1;
+            }
+            y += 1;
+        }
+        rotated
     }
 }
 
@@ -342,7 +443,7 @@ impl Board {
 pub struct Tetrimino {
     pub kind: Mino,
     pub rotation: Rotation,
-    pub grid: Vec<Vec<bool>>,
+    pub grid: Shape,
     pub offset_x: i8,
     pub offset_y: i8,
 }
@@ -354,80 +455,11 @@ impl Tetrimino {
             rotation: Rotation::Zero,
             offset_x: x,
             offset_y: y,
-            grid: match kind {
-                Mino::Empty | Mino::Garbage => vec![],
-                Mino::O => vec![vec![true, true], vec![true, true]],
-                Mino::I => vec![
-                    vec![false, false, false, false],
-                    vec![true, true, true, true],
-                    vec![false, false, false, false],
-                    vec![false, false, false, false],
-                ],
-                Mino::T => vec![
-                    vec![false, true, false],
-                    vec![true, true, true],
-                    vec![false, false, false],
-                ],
-                Mino::L => vec![
-                    vec![false, false, true],
-                    vec![true, true, true],
-                    vec![false, false, false],
-                ],
-                Mino::J => vec![
-                    vec![true, false, false],
-                    vec![true, true, true],
-                    vec![false, false, false],
-                ],
-                Mino::S => vec![
-                    vec![false, true, true],
-                    vec![true, true, false],
-                    vec![false, false, false],
-                ],
-                Mino::Z => vec![
-                    vec![true, true, false],
-                    vec![false, true, true],
-                    vec![false, false, false],
-                ],
-            },
-        }
-    }
-
-    pub fn rotate_grid(&mut self, direction: Direction) {
-        match direction {
-            Direction::Cw => self.rotate_grid_cw(),
-            Direction::Ccw => self.rotate_grid_ccw(),
-        }
-    }
-
-    fn rotate_grid_cw(&mut self) {
-        if self.kind == Mino::O {
-            return;
-        }
-        let n = self.grid.len();
-        for i in 0..(n / 2) {
-            for j in i..(n - i - 1) {
-                let temp = self.grid[i][j];
-                self.grid[i][j] = self.grid[n - 1 - j][i];
-                self.grid[n - 1 - j][i] = self.grid[n - 1 - i][n - 1 - j];
-                self.grid[n - 1 - i][n - 1 - j] = self.grid[j][n - 1 - i];
-                self.grid[j][n - 1 - i] = temp;
-            }
+            grid: Srs.grid(kind, Rotation::Zero),
         }
     }
 
-    fn rotate_grid_ccw(&mut self) {
-        if self.kind == Mino::O {
-            return;
-        }
-        let n = self.grid.len();
-        for i in 0..(n / 2) {
-            for j in i..(n - i - 1) {
-                let temp = self.grid[i][j];
-                self.grid[i][j] = self.grid[j][n - 1 - i];
-                self.grid[j][n - 1 - i] = self.grid[n - 1 - i][n - 1 - j];
-                self.grid[n - 1 - i][n - 1 - j] = self.grid[n - 1 - j][i];
-                self.grid[n - 1 - j][i] = temp;
-            }
-        }
+    pub const fn rotate_grid(&mut self, direction: Direction) {
+        self.grid = self.grid.rotate(direction);
     }
 }
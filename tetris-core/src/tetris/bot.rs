@@ -0,0 +1,359 @@
+use std::collections::VecDeque;
+
+use rand::{Rng, rngs::SmallRng};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Action, AttackTable, BOARD_HEIGHT, BOARD_WIDTH, Board, Clear, Direction, Game, Lockdown,
+    RandomSeed, Rotation, RotationSystem, Tetrimino, game::t_spin, getrandom, placements,
+};
+
+/// How well a bot plays
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Difficulty {
+    /// Pieces per second, including the entry delay
+    pub pps: f32,
+    /// Chance to take a random placement instead of the best one, from 0 to 1
+    pub mistake_rate: f32,
+}
+
+impl Difficulty {
+    pub const EASY: Self = Self {
+        pps: 0.8,
+        mistake_rate: 0.15,
+    };
+
+    pub const MEDIUM: Self = Self {
+        pps: 1.5,
+        mistake_rate: 0.05,
+    };
+
+    pub const HARD: Self = Self {
+        pps: 3.,
+        mistake_rate: 0.,
+    };
+
+    /// Frames the bot spends on each piece
+    fn frames_per_piece(self) -> usize {
+        if self.pps <= 0. {
+            return usize::MAX;
+        }
+        (60. / self.pps) as usize
+    }
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self::MEDIUM
+    }
+}
+
+/// How much each feature of a board counts, higher evaluations are better
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weights {
+    /// Per cell of the highest column
+    pub height: i32,
+    /// Per empty cell below the top of its column
+    pub holes: i32,
+    /// Per cell of height difference between neighbouring columns
+    pub bumpiness: i32,
+    /// Per cell of depth of the deepest well, up to 4
+    pub well: i32,
+    /// Per line sent to the opponent
+    pub attack: i32,
+    /// Per line cleared without sending anything
+    pub burn: i32,
+}
+
+impl Weights {
+    pub const DEFAULT: Self = Self {
+        height: -6,
+        holes: -40,
+        bumpiness: -5,
+        well: 4,
+        attack: 25,
+        burn: -6,
+    };
+
+    /// Scores the board after `piece` was placed on it, attack is counted with the game's
+    /// `attack_table`
+    pub fn evaluate(
+        &self,
+        board: &Board,
+        piece: &Tetrimino,
+        system: &dyn RotationSystem,
+        attack_table: &AttackTable,
+        kick: Option<usize>,
+    ) -> i32 {
+        let t_spin = t_spin(board, piece, system, kick);
+        let mut board = board.clone();
+        board.place(piece);
+        let lines = board.clear_lines();
+        let clear = Clear {
+            lines,
+            t_spin,
+            combo: None,
+            back_to_back: false,
+        };
+        let attack = i32::from(attack_table.attack(&clear, board.is_empty()));
+
+        let mut heights = [0; BOARD_WIDTH];
+        let mut holes = 0;
+        for (x, height) in heights.iter_mut().enumerate() {
+            let column = (0..BOARD_HEIGHT).map(|y| board.row(y) & 1 << x != 0);
+            let Some(top) = column.clone().position(|filled| filled) else {
+                continue;
+            };
+            *height = (BOARD_HEIGHT - top) as i32;
+            holes += column.skip(top).filter(|filled| !filled).count() as i32;
+        }
+        let bumpiness: i32 = heights.windows(2).map(|w| (w[0] - w[1]).abs()).sum();
+        let well = (0..BOARD_WIDTH)
+            .map(|x| {
+                let left = if x == 0 { i32::MAX } else { heights[x - 1] };
+                let right = heights.get(x + 1).copied().unwrap_or(i32::MAX);
+                (left.min(right) - heights[x]).clamp(0, 4)
+            })
+            .max()
+            .unwrap_or_default();
+        let height = heights.iter().copied().max().unwrap_or_default();
+        let burn = if attack == 0 { i32::from(lines) } else { 0 };
+
+        self.height * height
+            + self.holes * holes
+            + self.bumpiness * bumpiness
+            + self.well * well
+            + self.attack * attack
+            + self.burn * burn
+    }
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Plays a [`Game`] through the same actions as a player. Call [`Bot::actions`] every frame
+/// and pass the result to [`Game::user_actions`].
+#[derive(Debug, Clone)]
+pub struct Bot {
+    pub difficulty: Difficulty,
+    pub weights: Weights,
+    rng: SmallRng,
+    /// Remaining inputs for the current piece
+    plan: VecDeque<Action>,
+    /// Number of pieces locked when the plan was made
+    planned: Option<u32>,
+    /// Frames to wait before the hard drop
+    wait: usize,
+    /// Frame of the last hard drop
+    dropped: Option<u32>,
+    /// Column and rotation the piece should have after the last input
+    expected: Option<(i8, Rotation)>,
+}
+
+impl Bot {
+    pub fn new(difficulty: Difficulty, seed: Option<RandomSeed>) -> Self {
+        Self {
+            difficulty,
+            weights: Weights::default(),
+            rng: getrandom(seed),
+            plan: VecDeque::new(),
+            planned: None,
+            wait: 0,
+            dropped: None,
+            expected: None,
+        }
+    }
+
+    /// Inputs for the next frame of the game
+    pub fn actions(&mut self, game: &Game) -> Vec<Action> {
+        if !game.accepts_input() {
+            return vec![];
+        }
+        if self.planned != Some(game.pieces) {
+            self.planned = Some(game.pieces);
+            self.plan = self.plan(game).into();
+            // entry delay and line clears since the last piece count towards the speed
+            let since_drop = self.dropped.map_or(0, |frame| game.frame - frame) as usize;
+            let budget = self.difficulty.frames_per_piece();
+            self.wait = budget.saturating_sub(self.plan.len() + since_drop);
+        }
+        if self.plan.front() == Some(&Action::SoftDrop) {
+            if game.board.can_move_down(&mut game.piece.clone()) {
+                return vec![Action::SoftDrop];
+            }
+            self.plan.pop_front();
+        }
+        if self
+            .expected
+            .is_some_and(|expected| expected != (game.piece.offset_x, game.piece.rotation))
+        {
+            // the last input did not work out, like a move into a gap the piece fell past
+            self.plan = self.plan(game).into();
+            self.expected = None;
+        }
+        // the piece is moved first and waits above its spot, in case it locks on touch
+        if self.wait > 0 && self.plan.len() <= 1 {
+            self.wait -= 1;
+            return vec![];
+        }
+        let action = self.plan.pop_front().unwrap_or(Action::HardDrop);
+        let Some(expected) = Self::expect(game, action) else {
+            self.plan = self.plan(game).into();
+            self.expected = None;
+            return vec![];
+        };
+        self.expected = (action != Action::Hold).then_some(expected);
+        if action == Action::HardDrop {
+            self.dropped = Some(game.frame);
+        }
+        vec![action]
+    }
+
+    /// Column and rotation of the piece after `action`, `None` if it fails on the current board
+    fn expect(game: &Game, action: Action) -> Option<(i8, Rotation)> {
+        let mut piece = game.piece.clone();
+        let direction = match action {
+            Action::Cw => Direction::Cw,
+            Action::Ccw => Direction::Ccw,
+            Action::Rotate180 => Direction::Half,
+            Action::Left | Action::Right => {
+                let offset = if action == Action::Left { -1 } else { 1 };
+                let moved = game.board.move_x(&mut piece, offset);
+                return moved.then_some((piece.offset_x, piece.rotation));
+            }
+            Action::Hold | Action::HardDrop | Action::SoftDrop => {
+                return Some((piece.offset_x, piece.rotation));
+            }
+        };
+        let system = game.ruleset.rotation.system();
+        game.board
+            .rotate(&mut piece, system, game.can_floor_kick(), direction)
+            .map(|_| (piece.offset_x, piece.rotation))
+    }
+
+    /// Chooses where the current piece goes, or the hold piece if that is better
+    fn plan(&mut self, game: &Game) -> Vec<Action> {
+        let system = game.ruleset.rotation.system();
+        let mut candidates: Vec<(Vec<Action>, i32)> = vec![];
+        let held = game
+            .hold
+            .as_ref()
+            .or_else(|| game.next_queue.front())
+            .filter(|_| game.can_hold())
+            .map(|held| Game::spawn_position(&game.board, system, system.spawn(held.kind)));
+        let pieces = [Some(game.piece.clone()), held.flatten()];
+        for (hold, piece) in pieces.iter().enumerate() {
+            let Some(piece) = piece else {
+                continue;
+            };
+            // a held piece starts with a fresh floor kick
+            let can_floor_kick = hold == 1 || game.can_floor_kick();
+            for placement in placements(&game.board, piece, system, can_floor_kick) {
+                // pieces lock as soon as they land, there is no time to move them on the stack
+                if game.ruleset.lockdown == Lockdown::Classic
+                    && placement.path.contains(&Action::SoftDrop)
+                {
+                    continue;
+                }
+                let score = self.weights.evaluate(
+                    &game.board,
+                    &placement.piece,
+                    system,
+                    &game.attack_table,
+                    placement.kick,
+                );
+                let mut path = placement.path;
+                if hold == 1 {
+                    path.insert(0, Action::Hold);
+                }
+                candidates.push((path, score));
+            }
+        }
+        if candidates.is_empty() {
+            return vec![];
+        }
+        if self.rng.random::<f32>() < self.difficulty.mistake_rate {
+            let idx = self.rng.random_range(0..candidates.len());
+            return candidates.swap_remove(idx).0;
+        }
+        candidates
+            .into_iter()
+            .max_by_key(|(_, score)| *score)
+            .map(|(path, _)| path)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Bot, Difficulty};
+    use crate::tetris::{Action, Game, GameConfig, GameSettings, Gravity, Lockdown, Ruleset};
+
+    fn play(difficulty: Difficulty, frames: u32) -> Game {
+        let mut game = Game::new(GameConfig::with_seed(GameSettings::default(), [1; 32]));
+        let mut bot = Bot::new(difficulty, Some([2; 32]));
+        for _ in 0..frames {
+            let actions = bot.actions(&game);
+            game.user_actions(actions);
+            if game.done {
+                break;
+            }
+        }
+        game
+    }
+
+    #[test]
+    fn clears_lines_without_topping_out() {
+        let fast = Difficulty {
+            pps: 60.,
+            mistake_rate: 0.,
+        };
+        let game = play(fast, 60 * 60);
+        assert!(!game.done);
+        assert!(game.pieces > 100, "{} pieces", game.pieces);
+        assert!(
+            game.lines * 10 >= game.pieces * 4 - 40,
+            "{} lines",
+            game.lines
+        );
+    }
+
+    #[test]
+    fn keeps_to_its_speed() {
+        let game = play(Difficulty::HARD, 60 * 20);
+        assert!((58..=61).contains(&game.pieces), "{} pieces", game.pieces);
+    }
+
+    #[test]
+    fn follows_plan_with_floor_kicks_and_lock_on_touch() {
+        let lock_on_touch = Ruleset {
+            lockdown: Lockdown::Classic,
+            gravity: [Gravity::frames(1); 20],
+            ..Ruleset::GUIDELINE
+        };
+        for ruleset in [lock_on_touch, Ruleset::NES] {
+            let mut config = GameConfig::with_seed(GameSettings::default(), [1; 32]);
+            config.ruleset = ruleset;
+            let mut game = Game::new(config);
+            let slow = Difficulty {
+                pps: 1.,
+                mistake_rate: 0.,
+            };
+            let mut bot = Bot::new(slow, Some([2; 32]));
+            for _ in 0..60 * 60 {
+                let pieces = game.pieces;
+                let actions = bot.actions(&game);
+                game.user_actions(actions);
+                if game.pieces != pieces {
+                    // every piece locks where it was planned to, not where it happened to be
+                    assert!(bot.plan.iter().all(|action| *action == Action::HardDrop));
+                }
+            }
+            assert!(!game.done);
+            assert!(game.lines * 10 >= game.pieces * 3, "{} lines", game.lines);
+        }
+    }
+}
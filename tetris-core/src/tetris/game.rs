@@ -5,15 +5,32 @@ use rand::{SeedableRng, prelude::Rng, rngs::SmallRng};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
-use super::{Board, Direction, Mino, Tetrimino};
+use super::{
+    AttackTable, BOARD_HEIGHT, BOARD_WIDTH, Board, Direction, GarbageConfig, GarbageQueue, Goal,
+    Lockdown, Mino, Randomizer, RotationSystem, Ruleset, Tetrimino,
+};
 
-const LOCKDOWN_START: u8 = 30;
 const SOFT_FALL_MULT: u8 = 10;
-const LOCKDOWN_MOVES: u8 = 5;
-const LEVEL_GOAL: i8 = 5;
 
 pub type RandomSeed = [u8; 32];
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen)]
+pub enum GameMode {
+    #[default]
+    Marathon,
+    /// Clear 40 lines as fast as possible
+    Sprint,
+    /// Score as much as possible in 2 minutes
+    Ultra,
+    /// Clear 10 rows of garbage, ranked by time and pieces
+    Cheese,
+    /// Marathon that ends on level 15
+    Marathon15,
+    /// Endless marathon that reaches 20G and then gets faster
+    Master,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[cfg_attr(feature = "wasm-bindgen", wasm_bindgen)]
 pub struct GameSettings {
@@ -21,39 +38,69 @@ pub struct GameSettings {
     pub easy: bool,
     pub nes: bool,
     pub random: bool,
+    #[serde(default)]
+    pub mode: GameMode,
 }
 
 #[cfg_attr(feature = "wasm-bindgen", wasm_bindgen)]
 impl GameSettings {
     #[cfg_attr(feature = "wasm-bindgen", wasm_bindgen(constructor))]
-    pub fn new(jupiter: bool, easy: bool, nes: bool, random: bool) -> Self {
+    pub fn new(jupiter: bool, easy: bool, nes: bool, random: bool, mode: GameMode) -> Self {
         Self {
             jupiter,
             easy,
             nes,
             random,
+            mode,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct GameConfig {
     pub settings: GameSettings,
+    #[serde(default)]
+    pub ruleset: Ruleset,
     pub seed: Option<RandomSeed>,
+    #[serde(default)]
+    pub attack: AttackTable,
+    #[serde(default)]
+    pub garbage: GarbageConfig,
+    /// How many times faster than gravity a soft drop falls, part of the player's handling
+    #[serde(default = "default_soft_drop_factor")]
+    pub soft_drop_factor: u8,
+}
+
+const fn default_soft_drop_factor() -> u8 {
+    SOFT_FALL_MULT
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self::default_seed(GameSettings::default())
+    }
 }
 
 impl GameConfig {
     pub fn default_seed(settings: GameSettings) -> Self {
         Self {
             settings,
+            ruleset: settings.into(),
             seed: None,
+            attack: AttackTable::default(),
+            garbage: GarbageConfig::default(),
+            soft_drop_factor: SOFT_FALL_MULT,
         }
     }
 
     pub fn with_seed(settings: GameSettings, seed: RandomSeed) -> Self {
         Self {
             settings,
+            ruleset: settings.into(),
             seed: Some(seed),
+            attack: AttackTable::default(),
+            garbage: GarbageConfig::default(),
+            soft_drop_factor: SOFT_FALL_MULT,
         }
     }
 }
@@ -65,10 +112,70 @@ pub fn getrandom(seed: Option<RandomSeed>) -> SmallRng {
     }
 }
 
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+pub enum TSpin {
+    #[default]
+    None,
+    Mini,
+    Full,
+}
+
+/// Result of locking down a piece
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Clear {
+    pub lines: u8,
+    pub t_spin: TSpin,
+    /// Combo count after this clear, `None` if no lines were cleared
+    pub combo: Option<u8>,
+    /// If this clear continued a back-to-back chain
+    pub back_to_back: bool,
+}
+
+impl Clear {
+    /// Tetrises and T-spins that clear lines keep up back-to-back chains
+    pub const fn is_difficult(&self) -> bool {
+        self.lines == 4 || (self.lines > 0 && !matches!(self.t_spin, TSpin::None))
+    }
+
+    /// Points awarded for this clear before multiplying with the level
+    pub const fn points(&self) -> u32 {
+        let mut points = match (self.t_spin, self.lines) {
+            (TSpin::None, 1) | (TSpin::Mini, 0) => 100,
+            (TSpin::Mini, 1) => 200,
+            (TSpin::None, 2) => 300,
+            (TSpin::Full, 0) | (TSpin::Mini, 2) => 400,
+            (TSpin::None, 3) => 550,
+            (TSpin::None, 4) | (TSpin::Full, 1) => 800,
+            (TSpin::Full, 2) => 1200,
+            (TSpin::Full, 3) => 1600,
+            _ => 0,
+        };
+        if self.back_to_back {
+            points = points * 3 / 2;
+        }
+        if let Some(combo) = self.combo {
+            points += 50 * combo as u32;
+        }
+        points
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub enum Event {
-    Completion(u8),
+    Completion(Clear),
+    /// The piece cleared the whole board, always follows a completion
+    PerfectClear,
+    /// Lines to send to the opponent, after cancelling incoming garbage
+    Attack(u8),
     Gameover,
+    /// The goal of the ruleset was reached, ends the game
+    Finished {
+        frames: u32,
+    },
+    /// The time limit of the ruleset ran out, ends the game
+    TimeUp {
+        score: u32,
+    },
 }
 
 #[cfg_attr(feature = "wasm-bindgen", wasm_bindgen)]
@@ -81,9 +188,11 @@ pub enum Action {
     Hold,
     HardDrop,
     SoftDrop,
+    // appended so the packed encoding of the other actions stays the same
+    Rotate180,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Phase {
     Generation { frames_left: u8 },
     Falling { timer: u8 },
@@ -91,31 +200,48 @@ pub enum Phase {
     Completion,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Game {
     pub board: Board,
     pub piece: Tetrimino,
     pub ghost: Tetrimino,
     pub score: u32,
     pub level: u8,
-    bag: [Mino; 7],
-    bag_idx: usize,
+    /// Lines cleared so far
+    pub lines: u32,
+    /// Pieces locked so far
+    pub pieces: u32,
+    randomizer: Box<dyn Randomizer>,
+    rotation_system: &'static dyn RotationSystem,
     pub next_queue: VecDeque<Tetrimino>,
     pub hold: Option<Tetrimino>,
+    /// Number of consecutive line clears after the first one, `None` if the last piece did not
+    /// clear any lines
+    pub combo: Option<u8>,
+    /// If the last line clear was difficult, so the next difficult one gets a bonus
+    pub back_to_back: bool,
     can_hold: bool,
     can_floor_kick: bool,
+    /// Kick used by the last successful movement, if that movement was a rotation
+    last_kick: Option<usize>,
     pub phase: Phase,
     pub lockdown_timer: u8,
     lockdown_moves: u8,
     lockdown_y: i8,
-    level_goal: i8,
+    level_goal: i16,
     piece_rng: SmallRng,
     garbage_rng: SmallRng,
     pub done: bool,
-    pub garbage_slot: u8,
-    pub garbage_acc: u8,
+    pub paused: bool,
+    pub garbage: GarbageQueue,
+    /// Number of frames simulated so far
+    pub frame: u32,
     pub events: Vec<Event>,
+    /// Mode the game was started in, the rules come from [`Game::ruleset`]
     pub settings: GameSettings,
+    pub ruleset: Ruleset,
+    pub attack_table: AttackTable,
+    soft_drop_factor: u8,
 }
 
 impl Game {
@@ -125,40 +251,44 @@ impl Game {
             piece: Tetrimino::new(Mino::I, 0, 0),
             ghost: Tetrimino::new(Mino::I, 0, 0),
             score: 0,
-            bag: [
-                Mino::O,
-                Mino::I,
-                Mino::J,
-                Mino::L,
-                Mino::S,
-                Mino::Z,
-                Mino::T,
-            ],
+            lines: 0,
+            pieces: 0,
+            randomizer: config.ruleset.randomizer.build(),
+            rotation_system: config.ruleset.rotation.system(),
             next_queue: VecDeque::with_capacity(5),
-            bag_idx: 7,
             hold: None,
+            combo: None,
+            back_to_back: false,
             can_hold: true,
             can_floor_kick: true,
+            last_kick: None,
             level: 1,
             phase: Phase::Generation { frames_left: 0 },
-            lockdown_timer: LOCKDOWN_START,
-            lockdown_moves: LOCKDOWN_MOVES,
+            lockdown_timer: config.ruleset.lock_delay(1),
+            lockdown_moves: config.ruleset.lockdown.resets(),
             lockdown_y: 0,
-            level_goal: LEVEL_GOAL,
+            level_goal: i16::from(config.ruleset.level_lines),
             piece_rng: getrandom(config.seed),
             garbage_rng: getrandom(config.seed),
             done: false,
+            paused: false,
             events: vec![],
-            garbage_slot: 0,
-            garbage_acc: 0,
+            garbage: GarbageQueue::new(config.garbage),
+            frame: 0,
             settings: config.settings,
+            ruleset: config.ruleset,
+            attack_table: config.attack,
+            soft_drop_factor: config.soft_drop_factor.max(1),
         };
-        let mut rng = getrandom(config.seed);
-        new.garbage_slot = rng.random_range(1..9);
+
+        if let Goal::Dig(rows) = new.ruleset.goal {
+            new.add_cheese(rows);
+        }
 
         for _ in 0..5 {
             let next_kind = new.next_kind();
-            new.next_queue.push_back(Tetrimino::new(next_kind, 0, 0));
+            new.next_queue
+                .push_back(new.rotation_system.spawn(next_kind));
         }
         let get_next_piece = new.get_next_piece();
         new.next_piece(get_next_piece);
@@ -167,6 +297,17 @@ impl Game {
 
     pub fn user_actions(&mut self, user_actions: Vec<Action>) {
         self.events.clear();
+        if self.paused {
+            return;
+        }
+        self.frame += 1;
+        if let Goal::Time(_) = self.ruleset.goal
+            && self.finished()
+        {
+            self.done = true;
+            self.events.push(Event::TimeUp { score: self.score });
+            return;
+        }
         match self.phase {
             Phase::Generation { frames_left } => {
                 if frames_left == 0 {
@@ -175,7 +316,7 @@ impl Game {
                     self.start_fall();
                     self.can_hold = true;
                     self.can_floor_kick = true;
-                    self.lockdown_moves = LOCKDOWN_MOVES;
+                    self.lockdown_moves = self.ruleset.lockdown.resets();
                 } else {
                     self.phase = Phase::Generation {
                         frames_left: frames_left - 1,
@@ -194,25 +335,35 @@ impl Game {
                     if user_actions.contains(&Action::SoftDrop) {
                         self.score += self.level as u32;
                     }
-                    self.board.move_down(&mut self.piece);
+                    let mut fell = false;
+                    for _ in 0..self.ruleset.gravity(self.level).cells.max(1) {
+                        if !self.board.move_down(&mut self.piece) {
+                            break;
+                        }
+                        fell = true;
+                        self.last_kick = None;
+                    }
+                    if !fell && self.ruleset.lockdown == Lockdown::Classic {
+                        self.lock();
+                        return;
+                    }
                     if self.piece.offset_y > self.lockdown_y {
                         self.lockdown_y = self.piece.offset_y;
-                        self.lockdown_moves = LOCKDOWN_MOVES;
+                        self.step();
                     }
                     self.start_fall();
                 } else {
                     self.phase = Phase::Falling { timer: timer - 1 }
                 }
-                if !self.board.can_move_down(&mut self.piece) {
+                if self.ruleset.lockdown != Lockdown::Classic
+                    && !self.board.can_move_down(&mut self.piece)
+                {
                     self.phase = Phase::Lock;
                 }
             }
             Phase::Lock => {
-                if self.settings.nes || self.lockdown_timer == 0 {
-                    self.board.drop(&mut self.piece);
-                    self.board.place(&self.piece);
-                    self.phase = Phase::Completion;
-                    self.lockdown_timer = LOCKDOWN_START;
+                if self.lockdown_timer == 0 {
+                    self.lock();
                 } else {
                     self.process_input(&user_actions);
                     if self.board.can_move_down(&mut self.piece) {
@@ -223,31 +374,107 @@ impl Game {
                 }
             }
             Phase::Completion => {
+                self.pieces += 1;
+                let t_spin = self.t_spin();
                 let rows = self.board.clear_lines();
-                self.score += u32::from(self.level)
-                    * match rows {
-                        1 => 100,
-                        2 => 300,
-                        3 => 550,
-                        4 => 800,
-                        _ => 0,
-                    };
-                self.events.push(Event::Completion(rows));
-                if !self.settings.easy {
-                    self.level_goal -= rows as i8;
+                let mut clear = Clear {
+                    lines: rows,
+                    t_spin,
+                    combo: None,
+                    back_to_back: false,
+                };
+                if rows > 0 {
+                    self.combo = Some(self.combo.map_or(0, |combo| combo.saturating_add(1)));
+                    clear.combo = self.combo;
+                    clear.back_to_back = self.back_to_back && clear.is_difficult();
+                    self.back_to_back = clear.is_difficult();
+                } else {
+                    self.combo = None;
+                }
+                self.score += u32::from(self.level) * clear.points();
+                self.events.push(Event::Completion(clear));
+                let perfect_clear = rows > 0 && self.board.is_empty();
+                if perfect_clear {
+                    self.score += u32::from(self.level)
+                        * match rows {
+                            1 => 800,
+                            2 => 1200,
+                            3 => 1800,
+                            _ if clear.back_to_back => 3200,
+                            _ => 2000,
+                        };
+                    self.events.push(Event::PerfectClear);
+                }
+                let attack = self.attack_table.attack(&clear, perfect_clear);
+                let attack = self.garbage.cancel(attack);
+                if attack > 0 {
+                    self.events.push(Event::Attack(attack));
+                }
+                if self.ruleset.level_lines > 0 {
+                    self.level_goal -= i16::from(rows);
                     if self.level_goal <= 0 {
                         self.level = self.level.saturating_add(1);
-                        self.level_goal += LEVEL_GOAL;
+                        self.level_goal += i16::from(self.ruleset.level_lines);
                     }
                 }
+                self.lines += u32::from(rows);
+                if self.finished() {
+                    self.done = true;
+                    self.events.push(Event::Finished { frames: self.frame });
+                    return;
+                }
                 self.add_garbage();
-                self.phase = Phase::Generation { frames_left: 12 };
+                self.phase = Phase::Generation {
+                    frames_left: self.ruleset.entry_delay(self.level),
+                };
             }
         }
     }
 
+    /// Freezes the game until [`Game::resume`] is called. Returns if the game was paused.
+    pub const fn pause(&mut self) -> bool {
+        if self.done {
+            return false;
+        }
+        self.paused = true;
+        true
+    }
+
+    pub const fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// If the goal of the ruleset was reached
+    pub fn finished(&self) -> bool {
+        match self.ruleset.goal {
+            Goal::Endless => false,
+            Goal::Lines(lines) => self.lines >= u32::from(lines),
+            Goal::Time(frames) => self.frame > frames,
+            Goal::Dig(_) => !self.board.has_garbage(),
+            Goal::Level(level) => self.level >= level,
+        }
+    }
+
+    /// If actions passed to [`Game::user_actions`] on the next frame move the piece, false
+    /// during entry delay and line clears
+    pub const fn accepts_input(&self) -> bool {
+        !self.done && !self.paused && matches!(self.phase, Phase::Falling { .. } | Phase::Lock)
+    }
+
+    /// If the current piece can still be swapped with the hold piece
+    pub const fn can_hold(&self) -> bool {
+        self.can_hold
+    }
+
+    /// If the current piece can still use a kick that moves it up by two rows
+    pub const fn can_floor_kick(&self) -> bool {
+        self.can_floor_kick
+    }
+
+    /// Queues garbage received from the opponent, with a random hole for the whole batch
     pub fn accumulate_garbage(&mut self, lines: u8) {
-        self.garbage_acc += lines;
+        let hole = self.garbage_rng.random_range(0..BOARD_WIDTH as u8);
+        self.garbage.push(lines, hole, self.frame);
     }
 
     pub fn skip_completion(&mut self) -> Option<u8> {
@@ -257,8 +484,8 @@ impl Game {
                 .events
                 .iter()
                 .filter_map(|e| {
-                    if let Event::Completion(rows) = e {
-                        Some(*rows)
+                    if let Event::Completion(clear) = e {
+                        Some(clear.lines)
                     } else {
                         None
                     }
@@ -271,15 +498,25 @@ impl Game {
     }
 
     fn add_garbage(&mut self) {
-        self.board.push_up(self.garbage_acc);
-        for i in 0..self.garbage_acc {
-            let layer = 40 - i - 1;
-            self.board.add_garbage(layer, self.garbage_slot);
-            if self.garbage_rng.random_bool(0.3) {
-                self.garbage_slot = self.garbage_rng.random_range(0..10);
+        for batch in self.garbage.take(self.frame) {
+            if self.board.push_up(batch.lines) {
+                return self.gameover();
+            }
+            for i in 0..batch.lines {
+                let layer = BOARD_HEIGHT as u8 - i - 1;
+                self.board.add_garbage(layer, batch.hole);
             }
         }
-        self.garbage_acc = 0;
+    }
+
+    /// Fills the bottom of the board with garbage, the hole moves on every row
+    fn add_cheese(&mut self, rows: u8) {
+        let width = BOARD_WIDTH as u8;
+        let mut hole = self.garbage_rng.random_range(0..width);
+        for i in 0..rows.min(BOARD_HEIGHT as u8) {
+            self.board.add_garbage(BOARD_HEIGHT as u8 - i - 1, hole);
+            hole = (hole + self.garbage_rng.random_range(1..width)) % width;
+        }
     }
 
     fn process_input(&mut self, actions: &[Action]) {
@@ -301,6 +538,10 @@ impl Game {
                     let move_success = self.rotate(Direction::Ccw);
                     self.movement(move_success);
                 }
+                Action::Rotate180 => {
+                    let move_success = self.rotate(Direction::Half);
+                    self.movement(move_success);
+                }
                 Action::Hold => {
                     if !self.can_hold {
                         continue;
@@ -308,11 +549,13 @@ impl Game {
                     self.can_hold = false;
                     self.can_floor_kick = true;
                     if self.hold.is_none() {
-                        self.hold = Some(Tetrimino::new(self.piece.kind, 0, 0));
+                        self.hold = Some(self.rotation_system.spawn(self.piece.kind));
                         let piece = self.get_next_piece();
                         self.next_piece(piece);
                     } else {
-                        let piece = self.hold.replace(Tetrimino::new(self.piece.kind, 0, 0));
+                        let piece = self
+                            .hold
+                            .replace(self.rotation_system.spawn(self.piece.kind));
 
                         self.next_piece(piece.unwrap());
                     }
@@ -320,73 +563,112 @@ impl Game {
                 Action::HardDrop => {
                     let before = self.piece.offset_y;
                     self.board.drop(&mut self.piece);
+                    if self.piece.offset_y != before {
+                        self.last_kick = None;
+                    }
                     self.score +=
                         2 * self.level as u32 * (self.piece.offset_y - before).max(0) as u32;
-                    self.board.place(&self.piece);
-                    self.phase = Phase::Completion;
-                    self.lockdown_timer = LOCKDOWN_START;
+                    self.lock();
                 }
                 Action::SoftDrop => {
                     if let Phase::Falling { timer } = self.phase {
-                        let new = timer.saturating_sub(SOFT_FALL_MULT - 1);
+                        let new = timer.saturating_sub(self.soft_drop_factor - 1);
                         self.phase = Phase::Falling { timer: new }
                     }
                 }
             }
         }
-        if self.settings.jupiter
+        if self.ruleset.fall_factor > 1
             && !actions.contains(&Action::SoftDrop)
             && let Phase::Falling { timer } = self.phase
         {
-            let new = timer.saturating_sub(SOFT_FALL_MULT - 1);
+            let new = timer.saturating_sub(self.ruleset.fall_factor - 1);
             self.phase = Phase::Falling { timer: new }
         }
     }
 
-    const fn start_fall(&mut self) {
+    fn start_fall(&mut self) {
         self.phase = Phase::Falling {
-            timer: match self.level {
-                1 => 30,
-                2 => 20,
-                3 => 15,
-                4 => 10,
-                5 => 8,
-                6 => 6,
-                7 => 5,
-                8 => 4,
-                9 => 3,
-                10 => 2,
-                _ => 1,
-            },
+            timer: self.ruleset.gravity(self.level).frames,
         }
     }
 
+    fn lock(&mut self) {
+        self.board.drop(&mut self.piece);
+        self.board.place(&self.piece);
+        self.phase = Phase::Completion;
+        self.lockdown_timer = self.ruleset.lock_delay(self.level);
+    }
+
+    /// Resets the lock delay after a move or rotation
     fn movement(&mut self, move_success: bool) {
-        if move_success && self.phase == Phase::Lock && self.lockdown_moves > 0 {
-            self.lockdown_timer = LOCKDOWN_START;
-            self.lockdown_moves -= 1;
+        match self.ruleset.lockdown {
+            Lockdown::Infinity => {
+                if move_success && self.phase == Phase::Lock {
+                    self.lockdown_timer = self.ruleset.lock_delay(self.level);
+                }
+            }
+            Lockdown::MoveReset(_) => {
+                if move_success && self.phase == Phase::Lock && self.lockdown_moves > 0 {
+                    self.lockdown_timer = self.ruleset.lock_delay(self.level);
+                    self.lockdown_moves -= 1;
+                }
+                if self.lockdown_moves == 0 {
+                    self.lockdown_timer = 0;
+                }
+            }
+            Lockdown::StepReset | Lockdown::Classic => {}
         }
-        if self.lockdown_moves == 0 {
-            self.lockdown_timer = 0;
+    }
+
+    /// Resets the lock delay after the piece fell to a new lowest row
+    fn step(&mut self) {
+        match self.ruleset.lockdown {
+            Lockdown::MoveReset(resets) => self.lockdown_moves = resets,
+            Lockdown::Infinity | Lockdown::StepReset => {
+                self.lockdown_timer = self.ruleset.lock_delay(self.level);
+            }
+            Lockdown::Classic => {}
         }
     }
 
     fn move_x(&mut self, offset: i8) -> bool {
         let success = self.board.move_x(&mut self.piece, offset);
+        if success {
+            self.last_kick = None;
+        }
         self.update_ghost();
         success
     }
 
     fn rotate(&mut self, direction: Direction) -> bool {
         let prev_y = self.piece.offset_y;
-        let success = self
-            .board
-            .rotate(&mut self.piece, self.can_floor_kick, direction);
+        let kick = self.board.rotate(
+            &mut self.piece,
+            self.rotation_system,
+            self.can_floor_kick,
+            direction,
+        );
         self.update_ghost();
-        if success && self.piece.offset_y + 2 == prev_y {
+        if kick.is_some() {
+            // only the last kick of the 90 degree tables upgrades a T-spin mini
+            self.last_kick = kick.filter(|_| direction != Direction::Half).or(Some(0));
+        }
+        if kick.is_some() && self.piece.offset_y + 2 == prev_y {
             self.can_floor_kick = false;
         }
-        success
+        kick.is_some()
+    }
+
+    /// Checks the locked piece for a T-spin using the 3-corner rule. The last movement has to be a
+    /// rotation, and using the last kick of the table always counts as a full T-spin.
+    fn t_spin(&self) -> TSpin {
+        t_spin(
+            &self.board,
+            &self.piece,
+            self.rotation_system,
+            self.last_kick,
+        )
     }
 
     fn update_ghost(&mut self) {
@@ -396,58 +678,41 @@ impl Game {
     }
 
     fn next_kind(&mut self) -> Mino {
-        if self.settings.random {
-            return self.bag[self.piece_rng.random_range(0..7)];
-        }
-        if self.bag_idx < 7 {
-            let next = self.bag[self.bag_idx];
-            self.bag_idx += 1;
-            return next;
-        }
-        for i in 0..7 {
-            let swap = self.piece_rng.random_range(i..7);
-            self.bag.swap(i, swap);
-        }
-        self.bag_idx = 1;
-        self.bag[0]
+        self.randomizer.next(&mut self.piece_rng)
     }
 
-    const fn place_next_piece(tetrimino: &mut Tetrimino) {
-        let (x, y) = match tetrimino.kind {
-            Mino::Empty | Mino::Garbage => (0, 0),
-            Mino::O => (4, 18),
-            Mino::I => (3, 19),
-            Mino::L | Mino::J | Mino::S | Mino::Z | Mino::T => (3, 18),
-        };
-        tetrimino.offset_x = x;
-        tetrimino.offset_y = y;
+    fn place_next_piece(system: &dyn RotationSystem, tetrimino: &mut Tetrimino) {
+        (tetrimino.offset_x, tetrimino.offset_y) = system.spawn_offset(tetrimino.kind);
     }
 
-    fn next_piece(&mut self, mut piece: Tetrimino) {
-        Self::place_next_piece(&mut piece);
-        if !self.board.can_place(&piece) {
-            return self.gameover();
-        }
-        let moved = self.board.move_down(&mut piece);
-        if !moved {
+    /// Where a new piece appears on the board, `None` if there is no room for it or it would lock
+    /// down above the playfield
+    pub(crate) fn spawn_position(
+        board: &Board,
+        system: &dyn RotationSystem,
+        mut piece: Tetrimino,
+    ) -> Option<Tetrimino> {
+        Self::place_next_piece(system, &mut piece);
+        let fits = board.can_place(&piece) && board.move_down(&mut piece);
+        (fits && board.can_move_down(&mut piece)).then_some(piece)
+    }
+
+    fn next_piece(&mut self, piece: Tetrimino) {
+        let Some(piece) = Self::spawn_position(&self.board, self.rotation_system, piece) else {
             return self.gameover();
-        }
+        };
         let mut ghost = piece.clone();
         self.board.drop(&mut ghost);
-        // no drop - lock down above playfield
-        if ghost.offset_x == piece.offset_x && ghost.offset_y == piece.offset_y {
-            return self.gameover();
-        }
-
         self.ghost = ghost;
         self.lockdown_y = piece.offset_y;
+        self.last_kick = None;
         self.piece = piece;
     }
 
     fn get_next_piece(&mut self) -> Tetrimino {
         let piece = self.next_queue.pop_front().expect("Next queue was empty");
         let kind = self.next_kind();
-        self.next_queue.push_back(Tetrimino::new(kind, 0, 0));
+        self.next_queue.push_back(self.rotation_system.spawn(kind));
         piece
     }
 
@@ -456,3 +721,438 @@ impl Game {
         self.events.push(Event::Gameover);
     }
 }
+
+/// Checks for a T spin of `piece` locked on `board`, `kick` is the kick index of the last
+/// rotation like [`Game::last_kick`]
+pub(crate) fn t_spin(
+    board: &Board,
+    piece: &Tetrimino,
+    system: &dyn RotationSystem,
+    kick: Option<usize>,
+) -> TSpin {
+    let Some(kick) = kick else {
+        return TSpin::None;
+    };
+    if piece.kind != Mino::T {
+        return TSpin::None;
+    }
+    let (center_x, center_y) = system.t_center(piece.rotation);
+    let (x, y) = (piece.offset_x + center_x, piece.offset_y + center_y);
+    // clockwise, starting top left
+    let corners = [
+        (x - 1, y - 1),
+        (x + 1, y - 1),
+        (x + 1, y + 1),
+        (x - 1, y + 1),
+    ]
+    .map(|(x, y)| board.is_occupied(x, y));
+    if corners.iter().filter(|c| **c).count() < 3 {
+        return TSpin::None;
+    }
+    // the T points away from the side next to the center that is empty, same order as corners
+    let sides = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+    let back = sides
+        .iter()
+        .position(|&(dx, dy)| {
+            let (x, y) = (center_x + dx, center_y + dy);
+            !piece.grid.is_filled(x as usize, y as usize)
+        })
+        .unwrap_or_default();
+    let point = (back + 2) % 4;
+    let front = [point, (point + 1) % 4];
+    if front.iter().all(|i| corners[*i]) || kick == 4 {
+        TSpin::Full
+    } else {
+        TSpin::Mini
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Action, Clear, Event, Game, GameConfig, GameMode, GameSettings, Goal, Lockdown, Phase,
+        TSpin,
+    };
+    use crate::tetris::{
+        Ars, Board, Direction, Mino, Rotation, RotationSystem, RotationSystemKind, Tetrimino,
+    };
+
+    fn game() -> Game {
+        Game::new(GameConfig::with_seed(GameSettings::default(), [0; 32]))
+    }
+
+    fn fill_row(game: &mut Game, y: usize, holes: &[usize]) {
+        for x in 0..10 {
+            if !holes.contains(&x) {
+                game.board.set(x, y, Mino::Garbage);
+            }
+        }
+    }
+
+    /// Locks a T piece into the slot at the bottom of the board, as if the last movement was a
+    /// rotation using `kick`
+    fn lock_t(game: &mut Game, rotation: Rotation, kick: usize) {
+        let mut piece = Tetrimino::new(Mino::T, 1, 37);
+        while piece.rotation != rotation {
+            piece.rotate_grid(Direction::Cw);
+            piece.rotation = piece.rotation.rotate(Direction::Cw);
+        }
+        game.board.place(&piece);
+        game.piece = piece;
+        game.last_kick = Some(kick);
+        game.phase = Phase::Completion;
+        game.user_actions(vec![]);
+    }
+
+    #[test]
+    fn t_spin_double() {
+        let mut game = game();
+        fill_row(&mut game, 37, &[0, 1, 2]);
+        fill_row(&mut game, 38, &[1, 2, 3]);
+        fill_row(&mut game, 39, &[2]);
+        lock_t(&mut game, Rotation::Two, 0);
+        assert_eq!(
+            game.events,
+            vec![
+                Event::Completion(Clear {
+                    lines: 2,
+                    t_spin: TSpin::Full,
+                    combo: Some(0),
+                    back_to_back: false,
+                }),
+                Event::Attack(4)
+            ]
+        );
+        assert_eq!(game.score, 1200);
+    }
+
+    #[test]
+    fn t_spin_mini() {
+        let mut game = game();
+        // only one of the two front corners is filled
+        fill_row(&mut game, 37, &[5]);
+        fill_row(&mut game, 38, &[1, 2, 3]);
+        fill_row(&mut game, 39, &[2, 3]);
+        lock_t(&mut game, Rotation::Two, 0);
+        assert_eq!(
+            game.events,
+            vec![Event::Completion(Clear {
+                lines: 1,
+                t_spin: TSpin::Mini,
+                combo: Some(0),
+                back_to_back: false,
+            })]
+        );
+        assert_eq!(game.score, 200);
+    }
+
+    #[test]
+    fn last_kick_upgrades_mini() {
+        let mut game = game();
+        fill_row(&mut game, 37, &[5]);
+        fill_row(&mut game, 38, &[1, 2, 3]);
+        fill_row(&mut game, 39, &[2, 3]);
+        lock_t(&mut game, Rotation::Two, 4);
+        assert_eq!(
+            game.events,
+            vec![
+                Event::Completion(Clear {
+                    lines: 1,
+                    t_spin: TSpin::Full,
+                    combo: Some(0),
+                    back_to_back: false,
+                }),
+                Event::Attack(2)
+            ]
+        );
+    }
+
+    #[test]
+    fn ars_t_spin_uses_its_center() {
+        let mut config = GameConfig::with_seed(GameSettings::default(), [0; 32]);
+        config.ruleset.rotation = RotationSystemKind::Ars;
+        let mut game = Game::new(config);
+        // the upwards T of ARS sits on the bottom of its box, the center is at (1, 2)
+        game.board.set(0, 37, Mino::Garbage);
+        game.board.set(2, 37, Mino::Garbage);
+        fill_row(&mut game, 38, &[0, 1, 2]);
+        fill_row(&mut game, 39, &[5]);
+        let piece = Tetrimino {
+            rotation: Rotation::Two,
+            grid: Ars.grid(Mino::T, Rotation::Two),
+            offset_x: 0,
+            offset_y: 36,
+            ..Ars.spawn(Mino::T)
+        };
+        game.board.place(&piece);
+        game.piece = piece;
+        game.last_kick = Some(0);
+        game.phase = Phase::Completion;
+        game.user_actions(vec![]);
+        assert_eq!(
+            game.events.first(),
+            Some(&Event::Completion(Clear {
+                lines: 1,
+                t_spin: TSpin::Full,
+                combo: Some(0),
+                back_to_back: false,
+            }))
+        );
+    }
+
+    /// Locks a vertical I piece into the well at the right
+    fn lock_i(game: &mut Game) {
+        let mut piece = Tetrimino::new(Mino::I, 7, 36);
+        piece.rotate_grid(Direction::Cw);
+        piece.rotation = Rotation::Right;
+        game.board.place(&piece);
+        game.piece = piece;
+        game.phase = Phase::Completion;
+        game.user_actions(vec![]);
+    }
+
+    #[test]
+    fn back_to_back_tetris() {
+        let mut game = game();
+        for y in 36..40 {
+            fill_row(&mut game, y, &[9]);
+        }
+        // avoid a perfect clear
+        game.board.set(0, 35, Mino::Garbage);
+        lock_i(&mut game);
+        assert_eq!(game.score, 800);
+        assert!(game.back_to_back);
+        for y in 36..40 {
+            fill_row(&mut game, y, &[9]);
+        }
+        game.board.set(0, 35, Mino::Garbage);
+        lock_i(&mut game);
+        // 1200 for the back-to-back tetris, 50 for the combo
+        assert_eq!(game.score, 800 + 1250);
+    }
+
+    #[test]
+    fn combo_breaks() {
+        let mut game = game();
+        for y in 36..40 {
+            fill_row(&mut game, y, &[9]);
+        }
+        fill_row(&mut game, 35, &[9]);
+        lock_i(&mut game);
+        assert_eq!(game.combo, Some(0));
+        lock_i(&mut game);
+        assert_eq!(game.combo, Some(1));
+        lock_i(&mut game);
+        assert_eq!(game.combo, None);
+        assert!(!game.back_to_back);
+    }
+
+    #[test]
+    fn perfect_clear() {
+        let mut game = game();
+        for y in 36..40 {
+            fill_row(&mut game, y, &[9]);
+        }
+        lock_i(&mut game);
+        assert!(game.board.is_empty());
+        assert!(game.events.contains(&Event::PerfectClear));
+        assert_eq!(game.events.last(), Some(&Event::Attack(14)));
+        assert_eq!(game.score, 800 + 2000);
+    }
+
+    #[test]
+    fn attack_cancels_garbage() {
+        let mut game = game();
+        for y in 36..40 {
+            fill_row(&mut game, y, &[9]);
+        }
+        game.board.set(0, 35, Mino::Garbage);
+        game.accumulate_garbage(3);
+        lock_i(&mut game);
+        assert_eq!(game.garbage.pending(), 0);
+        assert_eq!(game.events.last(), Some(&Event::Attack(1)));
+    }
+
+    #[test]
+    fn garbage_enters_after_delay() {
+        let mut game = game();
+        game.accumulate_garbage(2);
+        game.frame += game.garbage.config.delay;
+        game.phase = Phase::Completion;
+        game.user_actions(vec![]);
+        assert!(game.garbage.batches.is_empty());
+        for y in 38..40 {
+            assert_eq!(
+                game.board.buffer()[y]
+                    .iter()
+                    .filter(|m| **m == Mino::Empty)
+                    .count(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn oversized_garbage_tops_out() {
+        let mut config = GameConfig::with_seed(GameSettings::default(), [0; 32]);
+        config.garbage.cap = u8::MAX;
+        let mut game = Game::new(config);
+        assert_eq!(game.garbage.config.cap, 40);
+        game.board.set(0, 39, Mino::Garbage);
+        game.accumulate_garbage(200);
+        game.frame += game.garbage.config.delay;
+        game.phase = Phase::Completion;
+        game.user_actions(vec![]);
+        assert!(game.done);
+    }
+
+    #[test]
+    fn pause_freezes_game() {
+        let mut game = game();
+        game.user_actions(vec![]);
+        let phase = game.phase.clone();
+        assert!(game.pause());
+        for _ in 0..100 {
+            game.user_actions(vec![Action::HardDrop]);
+        }
+        assert_eq!(game.phase, phase);
+        assert_eq!(game.frame, 1);
+        game.resume();
+        game.user_actions(vec![]);
+        assert_eq!(game.frame, 2);
+    }
+
+    #[test]
+    fn sprint_finishes_at_goal() {
+        let settings = GameSettings {
+            mode: GameMode::Sprint,
+            ..GameSettings::default()
+        };
+        let mut game = Game::new(GameConfig::with_seed(settings, [0; 32]));
+        game.lines = 36;
+        for y in 36..40 {
+            fill_row(&mut game, y, &[9]);
+        }
+        game.board.set(0, 35, Mino::Garbage);
+        lock_i(&mut game);
+        assert!(game.done);
+        assert!(game.finished());
+        assert_eq!(
+            game.events.last(),
+            Some(&Event::Finished { frames: game.frame })
+        );
+    }
+
+    #[test]
+    fn ultra_ends_after_time_limit() {
+        let mut game = game();
+        game.ruleset.goal = Goal::Time(10);
+        for _ in 0..10 {
+            game.user_actions(vec![]);
+        }
+        assert!(!game.done);
+        game.user_actions(vec![]);
+        assert!(game.done);
+        assert_eq!(game.events, [Event::TimeUp { score: 0 }]);
+    }
+
+    #[test]
+    fn cheese_finishes_when_garbage_is_cleared() {
+        let settings = GameSettings {
+            mode: GameMode::Cheese,
+            ..GameSettings::default()
+        };
+        let mut game = Game::new(GameConfig::with_seed(settings, [0; 32]));
+        let rows = game.board.buffer().iter();
+        assert_eq!(rows.filter(|row| row.contains(&Mino::Garbage)).count(), 10);
+        assert!(!game.finished());
+
+        game.board = Board::new();
+        game.board.add_garbage(39, 9);
+        lock_i(&mut game);
+        assert!(game.done);
+        assert_eq!(game.pieces, 1);
+        assert_eq!(
+            game.events.last(),
+            Some(&Event::Finished { frames: game.frame })
+        );
+    }
+
+    #[test]
+    fn twenty_g_drops_to_floor() {
+        let settings = GameSettings {
+            mode: GameMode::Master,
+            ..GameSettings::default()
+        };
+        let mut game = Game::new(GameConfig::with_seed(settings, [0; 32]));
+        while !matches!(game.phase, Phase::Falling { .. }) {
+            game.user_actions(vec![]);
+        }
+        game.level = 20;
+        game.phase = Phase::Falling { timer: 0 };
+        game.user_actions(vec![]);
+        let mut piece = game.piece.clone();
+        assert!(!game.board.move_down(&mut piece));
+    }
+
+    #[test]
+    fn level_cap_ends_marathon() {
+        let settings = GameSettings {
+            mode: GameMode::Marathon15,
+            ..GameSettings::default()
+        };
+        let mut game = Game::new(GameConfig::with_seed(settings, [0; 32]));
+        game.level = 14;
+        game.level_goal = 4;
+        for y in 36..40 {
+            fill_row(&mut game, y, &[9]);
+        }
+        game.board.set(0, 35, Mino::Garbage);
+        lock_i(&mut game);
+        assert_eq!(game.level, 15);
+        assert!(game.done);
+        assert_eq!(
+            game.events.last(),
+            Some(&Event::Finished { frames: game.frame })
+        );
+    }
+
+    /// Frames a piece resting on the floor survives before it locks, tapping left and right if
+    /// `wiggle` is set
+    fn frames_on_floor(lockdown: Lockdown, wiggle: bool) -> u32 {
+        let mut game = game();
+        game.ruleset.lockdown = lockdown;
+        game.lockdown_moves = lockdown.resets();
+        while !matches!(game.phase, Phase::Falling { .. }) {
+            game.user_actions(vec![]);
+        }
+        game.board.drop(&mut game.piece);
+        game.lockdown_y = game.piece.offset_y;
+        for frame in 0..1000 {
+            let actions = match frame % 2 {
+                _ if !wiggle => vec![],
+                0 => vec![Action::Left],
+                _ => vec![Action::Right],
+            };
+            game.user_actions(actions);
+            if game.phase == Phase::Completion {
+                return frame;
+            }
+        }
+        1000
+    }
+
+    #[test]
+    fn lockdown_policies() {
+        // gravity on level 1 tries to move the piece every 31 frames
+        assert_eq!(frames_on_floor(Lockdown::Classic, false), 30);
+        assert_eq!(frames_on_floor(Lockdown::Classic, true), 30);
+        // lock delay of 30 frames
+        assert_eq!(frames_on_floor(Lockdown::StepReset, false), 31);
+        assert_eq!(frames_on_floor(Lockdown::StepReset, true), 31);
+        assert_eq!(frames_on_floor(Lockdown::MoveReset(5), false), 31);
+        assert_eq!(frames_on_floor(Lockdown::MoveReset(5), true), 6);
+        assert_eq!(frames_on_floor(Lockdown::Infinity, false), 31);
+        assert_eq!(frames_on_floor(Lockdown::Infinity, true), 1000);
+    }
+}
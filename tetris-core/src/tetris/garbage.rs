@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use super::BOARD_HEIGHT;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GarbageConfig {
+    /// Frames between receiving garbage and it being able to enter the board
+    pub delay: u32,
+    /// Maximum lines entering the board per piece, the rest stays queued
+    pub cap: u8,
+}
+
+impl Default for GarbageConfig {
+    fn default() -> Self {
+        Self { delay: 20, cap: 8 }
+    }
+}
+
+/// A single attack received from the opponent
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GarbageBatch {
+    pub lines: u8,
+    /// Column of the hole in every row of this batch
+    pub hole: u8,
+    /// Frame from which the batch can enter the board
+    pub arrival: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GarbageQueue {
+    pub config: GarbageConfig,
+    pub batches: VecDeque<GarbageBatch>,
+}
+
+impl GarbageQueue {
+    /// The cap is limited to the height of the board
+    pub const fn new(mut config: GarbageConfig) -> Self {
+        if config.cap as usize > BOARD_HEIGHT {
+            config.cap = BOARD_HEIGHT as u8;
+        }
+        Self {
+            config,
+            batches: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, lines: u8, hole: u8, frame: u32) {
+        if lines == 0 {
+            return;
+        }
+        self.batches.push_back(GarbageBatch {
+            lines,
+            hole,
+            arrival: frame + self.config.delay,
+        });
+    }
+
+    /// Total number of queued lines, including those that have not arrived yet
+    pub fn pending(&self) -> u32 {
+        self.batches.iter().map(|b| u32::from(b.lines)).sum()
+    }
+
+    /// Cancels queued lines with an outgoing attack, oldest first. Returns the part of the attack
+    /// that was not used up.
+    pub fn cancel(&mut self, mut attack: u8) -> u8 {
+        while attack > 0
+            && let Some(batch) = self.batches.front_mut()
+        {
+            let cancelled = attack.min(batch.lines);
+            attack -= cancelled;
+            batch.lines -= cancelled;
+            if batch.lines == 0 {
+                self.batches.pop_front();
+            }
+        }
+        attack
+    }
+
+    /// Removes the batches that have arrived by `frame`, up to the per piece cap. Batches that
+    /// only fit partially are split.
+    pub fn take(&mut self, frame: u32) -> Vec<GarbageBatch> {
+        let mut left = self.config.cap;
+        let mut taken = vec![];
+        while left > 0
+            && let Some(batch) = self.batches.front_mut()
+            && batch.arrival <= frame
+        {
+            let lines = left.min(batch.lines);
+            left -= lines;
+            batch.lines -= lines;
+            taken.push(GarbageBatch { lines, ..*batch });
+            if batch.lines == 0 {
+                self.batches.pop_front();
+            }
+        }
+        taken
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GarbageBatch, GarbageConfig, GarbageQueue};
+
+    #[test]
+    fn delay_and_cap() {
+        let mut queue = GarbageQueue::new(GarbageConfig { delay: 10, cap: 4 });
+        queue.push(3, 1, 0);
+        queue.push(3, 5, 5);
+        assert!(queue.take(9).is_empty());
+        assert_eq!(
+            queue.take(10),
+            vec![GarbageBatch {
+                lines: 3,
+                hole: 1,
+                arrival: 10
+            }]
+        );
+        queue.push(2, 7, 10);
+        assert_eq!(queue.pending(), 5);
+        assert_eq!(
+            queue.take(100),
+            vec![
+                GarbageBatch {
+                    lines: 3,
+                    hole: 5,
+                    arrival: 15
+                },
+                GarbageBatch {
+                    lines: 1,
+                    hole: 7,
+                    arrival: 20
+                }
+            ]
+        );
+        assert_eq!(queue.pending(), 1);
+    }
+
+    #[test]
+    fn cancel_oldest_first() {
+        let mut queue = GarbageQueue::new(GarbageConfig::default());
+        queue.push(2, 0, 0);
+        queue.push(4, 3, 0);
+        assert_eq!(queue.cancel(3), 0);
+        assert_eq!(queue.batches.len(), 1);
+        assert_eq!(queue.pending(), 3);
+        assert_eq!(queue.cancel(5), 2);
+        assert_eq!(queue.pending(), 0);
+    }
+}
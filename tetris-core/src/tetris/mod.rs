@@ -1,23 +1,39 @@
+mod attack;
 mod board;
+mod bot;
 mod game;
+mod garbage;
+mod movegen;
+mod randomizer;
+mod replay;
+mod rotation;
+mod ruleset;
 
+pub use attack::*;
 pub use board::*;
+pub use bot::*;
 pub use game::*;
+pub use garbage::*;
+pub use movegen::*;
+pub use randomizer::*;
+pub use replay::*;
+pub use rotation::*;
+pub use ruleset::*;
 
 #[cfg(test)]
 mod test {
     use serde_test::{Token, assert_tokens};
 
-    use super::{Board, Mino};
+    use super::{Board, Direction, Mino, Tetrimino};
 
     #[test]
     fn test_ser_de() {
         let mut board = Board::default();
-        board.buffer[0][1] = Mino::Garbage;
-        board.buffer[0][2] = Mino::I;
-        board.buffer[0][3] = Mino::J;
-        board.buffer[0][4] = Mino::Z;
-        board.buffer[0][5] = Mino::O;
+        board.set(1, 0, Mino::Garbage);
+        board.set(2, 0, Mino::I);
+        board.set(3, 0, Mino::J);
+        board.set(4, 0, Mino::Z);
+        board.set(5, 0, Mino::O);
 
         let mut tokens = vec![
             Token::Tuple { len: 400 },
@@ -55,4 +71,25 @@ mod test {
         tokens.push(Token::TupleEnd);
         assert_tokens(&board, &tokens);
     }
+
+    #[test]
+    fn row_masks_follow_the_board() {
+        let mut board = Board::new();
+        board.add_garbage(39, 0);
+        board.add_garbage(38, 0);
+        let mut piece = Tetrimino::new(Mino::I, 0, 36);
+        assert!(!board.can_move_down(&mut piece));
+        assert!(!board.move_x(&mut piece, -1));
+        piece.rotate_grid(Direction::Cw);
+        piece.offset_x = -2;
+        assert!(board.can_place(&piece));
+        board.drop(&mut piece);
+        assert_eq!(piece.offset_y, 36);
+        board.place(&piece);
+        assert_eq!(board.clear_lines(), 2);
+        assert_eq!(board.row(39), 0b1);
+        assert_eq!(board.row(38), 0b1);
+        assert!(!board.is_occupied(0, 37) && !board.is_occupied(1, 39));
+        assert!(board.is_occupied(-1, 0) && board.is_occupied(10, 0));
+    }
 }
@@ -0,0 +1,276 @@
+use std::collections::{HashSet, VecDeque};
+
+use super::{Action, Board, Direction, RotationSystem, Tetrimino, game::t_spin};
+
+/// A spot a piece can lock in, and how to get there
+#[derive(Debug, Clone)]
+pub struct Placement {
+    /// The piece resting on the stack
+    pub piece: Tetrimino,
+    /// Inputs from the spawn position, always ending with [`Action::HardDrop`]. A
+    /// [`Action::SoftDrop`] stands for holding soft drop until the piece lands.
+    pub path: Vec<Action>,
+    /// Kick index of the last input if it was a rotation, like `Game::last_kick`
+    pub kick: Option<usize>,
+}
+
+/// Where the cells of a piece end up, placements that fill the same cells with the same spin are
+/// the same
+fn cells(piece: &Tetrimino) -> Vec<(i8, i8)> {
+    piece
+        .grid
+        .cells()
+        .map(|(x, y)| (x as i8 + piece.offset_x, y as i8 + piece.offset_y))
+        .collect()
+}
+
+/// Finds every placement of `piece` that can be reached with moves, rotations and soft drops
+/// of the rotation system, with the shortest path to each of them. A T spin is a different
+/// placement than dropping into the same cells. Gravity is not taken into
+/// account, the piece only falls when soft dropped. `can_floor_kick` is the state of the game,
+/// like the game only one floor kick is allowed afterwards.
+pub fn placements(
+    board: &Board,
+    piece: &Tetrimino,
+    system: &dyn RotationSystem,
+    can_floor_kick: bool,
+) -> Vec<Placement> {
+    let mut placements = vec![];
+    let mut seen = HashSet::new();
+    let mut placed = HashSet::new();
+    let mut queue = VecDeque::new();
+    if !board.can_place(piece) {
+        return placements;
+    }
+    seen.insert((
+        piece.offset_x,
+        piece.offset_y,
+        piece.rotation,
+        can_floor_kick,
+        None,
+    ));
+    queue.push_back((piece.clone(), vec![], None, can_floor_kick));
+
+    while let Some((piece, path, kick, can_floor_kick)) = queue.pop_front() {
+        let mut resting = piece.clone();
+        if !board.can_move_down(&mut resting)
+            && placed.insert((cells(&piece), t_spin(board, &piece, system, kick)))
+        {
+            let mut path: Vec<Action> = path.clone();
+            if path.last() == Some(&Action::SoftDrop) {
+                path.pop();
+            }
+            path.push(Action::HardDrop);
+            placements.push(Placement {
+                piece: piece.clone(),
+                path,
+                kick,
+            });
+        }
+
+        for action in [
+            Action::Left,
+            Action::Right,
+            Action::Cw,
+            Action::Ccw,
+            Action::Rotate180,
+            Action::SoftDrop,
+        ] {
+            let mut next = piece.clone();
+            let mut next_kick = None;
+            let mut next_floor_kick = can_floor_kick;
+            let moved = match action {
+                Action::Left => board.move_x(&mut next, -1),
+                Action::Right => board.move_x(&mut next, 1),
+                Action::SoftDrop => {
+                    board.drop(&mut next);
+                    next.offset_y != piece.offset_y
+                }
+                Action::Cw | Action::Ccw | Action::Rotate180 => {
+                    let direction = match action {
+                        Action::Cw => Direction::Cw,
+                        Action::Ccw => Direction::Ccw,
+                        _ => Direction::Half,
+                    };
+                    let kick = board.rotate(&mut next, system, can_floor_kick, direction);
+                    // the same as `Game::rotate`
+                    next_kick = kick.map(|kick| {
+                        if direction == Direction::Half {
+                            0
+                        } else {
+                            kick
+                        }
+                    });
+                    if kick.is_some() && next.offset_y + 2 == piece.offset_y {
+                        next_floor_kick = false;
+                    }
+                    kick.is_some()
+                }
+                Action::Hold | Action::HardDrop => false,
+            };
+            // the kick is part of the state, rotating into a spot can spin where moving can't
+            let key = (
+                next.offset_x,
+                next.offset_y,
+                next.rotation,
+                next_floor_kick,
+                next_kick,
+            );
+            if !moved || !seen.insert(key) {
+                continue;
+            }
+            let mut next_path = path.clone();
+            next_path.push(action);
+            queue.push_back((next, next_path, next_kick, next_floor_kick));
+        }
+    }
+    placements
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Placement, placements};
+    use crate::tetris::{Action, Board, Direction, Mino, Srs, TSpin, Tetrimino, game::t_spin};
+
+    fn spawn(kind: Mino) -> Tetrimino {
+        Tetrimino::new(kind, 3, 19)
+    }
+
+    #[test]
+    fn every_column_and_rotation_on_empty_board() {
+        let board = Board::new();
+        for (kind, count) in [(Mino::T, 34), (Mino::O, 9), (Mino::I, 17), (Mino::S, 17)] {
+            assert_eq!(
+                placements(&board, &spawn(kind), &Srs, true).len(),
+                count,
+                "{kind:?}"
+            );
+        }
+    }
+
+    /// Moves `piece` along the path of `placement` like the game would
+    fn follow(
+        board: &Board,
+        mut piece: Tetrimino,
+        placement: &Placement,
+        mut can_floor_kick: bool,
+    ) -> Tetrimino {
+        for action in &placement.path {
+            let direction = match action {
+                Action::Cw => Direction::Cw,
+                Action::Ccw => Direction::Ccw,
+                Action::Rotate180 => Direction::Half,
+                Action::Left => {
+                    assert!(board.move_x(&mut piece, -1));
+                    continue;
+                }
+                Action::Right => {
+                    assert!(board.move_x(&mut piece, 1));
+                    continue;
+                }
+                Action::SoftDrop | Action::HardDrop | Action::Hold => {
+                    board.drop(&mut piece);
+                    continue;
+                }
+            };
+            let prev_y = piece.offset_y;
+            let kick = board.rotate(&mut piece, &Srs, can_floor_kick, direction);
+            assert!(kick.is_some());
+            if piece.offset_y + 2 == prev_y {
+                can_floor_kick = false;
+            }
+        }
+        piece
+    }
+
+    #[test]
+    fn paths_lead_to_placement() {
+        let mut board = Board::new();
+        for x in 0..10 {
+            if x != 4 {
+                board.set(x, 39, Mino::Garbage);
+            }
+            if !(3..6).contains(&x) {
+                board.set(x, 38, Mino::Garbage);
+            }
+        }
+        board.set(3, 37, Mino::Garbage);
+        board.set(2, 37, Mino::Garbage);
+        let placements = placements(&board, &spawn(Mino::T), &Srs, true);
+        // the T spin double into the slot needs a soft drop and a rotation at the bottom
+        let slot = placements
+            .iter()
+            .find(|p| {
+                let mut board = board.clone();
+                board.place(&p.piece);
+                board.clear_lines() == 2
+            })
+            .expect("no T spin double");
+        assert!(slot.kick.is_some());
+        assert!(slot.path.contains(&Action::SoftDrop));
+
+        for can_floor_kick in [true, false] {
+            for placement in &super::placements(&board, &spawn(Mino::T), &Srs, can_floor_kick) {
+                let piece = follow(&board, spawn(Mino::T), placement, can_floor_kick);
+                assert_eq!(super::cells(&piece), super::cells(&placement.piece));
+                if placement.kick.is_some()
+                    && placement.path.iter().rev().nth(1) == Some(&Action::Rotate180)
+                {
+                    assert_eq!(placement.kick, Some(0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn one_floor_kick_per_piece() {
+        let mut board = Board::new();
+        for (y, row) in [(37, "#.....####"), (38, ".#..######"), (39, ".##.#...##")] {
+            for (x, cell) in row.chars().enumerate() {
+                if cell == '#' {
+                    board.set(x, y, Mino::Garbage);
+                }
+            }
+        }
+        // the I piece lies in the pocket, climbing out of it takes two floor kicks
+        let piece = Tetrimino::new(Mino::I, 2, 36);
+        for can_floor_kick in [true, false] {
+            for placement in &placements(&board, &piece, &Srs, can_floor_kick) {
+                let moved = follow(&board, piece.clone(), placement, can_floor_kick);
+                assert_eq!(super::cells(&moved), super::cells(&placement.piece));
+            }
+        }
+
+        // the I lying on the floor can only stand up on the column with a floor kick
+        let mut board = Board::new();
+        board.set(3, 38, Mino::Garbage);
+        board.set(3, 39, Mino::Garbage);
+        let piece = Tetrimino::new(Mino::I, 6, 38);
+        let target = vec![(3, 34), (3, 35), (3, 36), (3, 37)];
+        let reaches = |can_floor_kick| {
+            placements(&board, &piece, &Srs, can_floor_kick)
+                .iter()
+                .any(|p| super::cells(&p.piece) == target)
+        };
+        assert!(reaches(true));
+        assert!(!reaches(false));
+    }
+
+    #[test]
+    fn t_spin_kept_next_to_drop() {
+        let mut board = Board::new();
+        for y in 37..40 {
+            board.set(1, y, Mino::Garbage);
+        }
+        let placements = placements(&board, &spawn(Mino::T), &Srs, true);
+        // a T pointing right on top of the column, the wall and the column fill three corners
+        let target = vec![(0, 35), (0, 36), (1, 36), (0, 37)];
+        let spins: Vec<TSpin> = placements
+            .iter()
+            .filter(|p| super::cells(&p.piece) == target)
+            .map(|p| t_spin(&board, &p.piece, &Srs, p.kick))
+            .collect();
+        assert!(spins.contains(&TSpin::None));
+        assert!(spins.iter().any(|&spin| spin != TSpin::None));
+    }
+}
@@ -0,0 +1,267 @@
+use std::fmt::Debug;
+
+use rand::{Rng, rngs::SmallRng};
+use serde::{Deserialize, Serialize};
+
+use super::Mino;
+
+/// All pieces in the order a fresh bag starts with
+pub const PIECES: [Mino; 7] = [
+    Mino::O,
+    Mino::I,
+    Mino::J,
+    Mino::L,
+    Mino::S,
+    Mino::Z,
+    Mino::T,
+];
+
+/// Generates the piece sequence of a game. All randomness has to come from `rng`, so games stay
+/// deterministic from their seed.
+pub trait Randomizer: Debug + Send {
+    fn next(&mut self, rng: &mut SmallRng) -> Mino;
+
+    fn clone_box(&self) -> Box<dyn Randomizer>;
+}
+
+impl Clone for Box<dyn Randomizer> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Selects the [`Randomizer`] of a game
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub enum RandomizerKind {
+    #[default]
+    SevenBag,
+    FourteenBag,
+    /// Every piece is equally likely
+    Uniform,
+    /// NES Tetris, rerolls once if the piece repeats
+    Nes,
+    /// Tetris The Grand Master, rerolls pieces that are in the last 4
+    History,
+    /// Repeats the given pieces, anything but the seven pieces is skipped
+    Fixed(Vec<Mino>),
+}
+
+impl RandomizerKind {
+    pub fn build(&self) -> Box<dyn Randomizer> {
+        match self {
+            Self::SevenBag => Box::new(Bag::new(1)),
+            Self::FourteenBag => Box::new(Bag::new(2)),
+            Self::Uniform => Box::new(Uniform),
+            Self::Nes => Box::new(Nes { last: None }),
+            Self::History => Box::new(History::new()),
+            Self::Fixed(sequence) => Box::new(Fixed {
+                sequence: sequence
+                    .iter()
+                    .copied()
+                    .filter(|piece| PIECES.contains(piece))
+                    .collect(),
+                idx: 0,
+            }),
+        }
+    }
+}
+
+/// Shuffles `copies` of every piece and deals them out before shuffling again
+#[derive(Debug, Clone)]
+pub struct Bag {
+    pieces: Vec<Mino>,
+    idx: usize,
+}
+
+impl Bag {
+    pub fn new(copies: usize) -> Self {
+        let pieces: Vec<Mino> = PIECES.repeat(copies);
+        Self {
+            idx: pieces.len(),
+            pieces,
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn next(&mut self, rng: &mut SmallRng) -> Mino {
+        if self.idx >= self.pieces.len() {
+            for i in 0..self.pieces.len() {
+                let swap = rng.random_range(i..self.pieces.len());
+                self.pieces.swap(i, swap);
+            }
+            self.idx = 0;
+        }
+        self.idx += 1;
+        self.pieces[self.idx - 1]
+    }
+
+    fn clone_box(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Uniform;
+
+impl Randomizer for Uniform {
+    fn next(&mut self, rng: &mut SmallRng) -> Mino {
+        PIECES[rng.random_range(0..PIECES.len())]
+    }
+
+    fn clone_box(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Nes {
+    last: Option<Mino>,
+}
+
+impl Randomizer for Nes {
+    fn next(&mut self, rng: &mut SmallRng) -> Mino {
+        // the extra roll stands for "reroll", like on the NES
+        let roll = rng.random_range(0..=PIECES.len());
+        let mut piece = PIECES.get(roll).copied();
+        if piece.is_none() || piece == self.last {
+            piece = Some(PIECES[rng.random_range(0..PIECES.len())]);
+        }
+        self.last = piece;
+        piece.unwrap_or_default()
+    }
+
+    fn clone_box(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct History {
+    history: [Mino; 4],
+    first: bool,
+}
+
+impl History {
+    const ROLLS: usize = 6;
+
+    pub const fn new() -> Self {
+        Self {
+            history: [Mino::Z, Mino::S, Mino::S, Mino::Z],
+            first: true,
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Randomizer for History {
+    fn next(&mut self, rng: &mut SmallRng) -> Mino {
+        let piece = if self.first {
+            // never start with an S, Z or O
+            self.first = false;
+            [Mino::I, Mino::J, Mino::L, Mino::T][rng.random_range(0..4)]
+        } else {
+            let mut piece = PIECES[rng.random_range(0..PIECES.len())];
+            for _ in 1..Self::ROLLS {
+                if !self.history.contains(&piece) {
+                    break;
+                }
+                piece = PIECES[rng.random_range(0..PIECES.len())];
+            }
+            piece
+        };
+        self.history.rotate_left(1);
+        self.history[3] = piece;
+        piece
+    }
+
+    fn clone_box(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+/// Repeats a user supplied sequence, falls back to I pieces if it is empty
+#[derive(Debug, Clone)]
+pub struct Fixed {
+    sequence: Vec<Mino>,
+    idx: usize,
+}
+
+impl Randomizer for Fixed {
+    fn next(&mut self, _rng: &mut SmallRng) -> Mino {
+        let Some(&piece) = self.sequence.get(self.idx) else {
+            return Mino::I;
+        };
+        self.idx = (self.idx + 1) % self.sequence.len();
+        piece
+    }
+
+    fn clone_box(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PIECES, RandomizerKind};
+    use crate::tetris::{Mino, getrandom};
+
+    fn sequence(kind: &RandomizerKind, len: usize) -> Vec<Mino> {
+        let mut rng = getrandom(Some([3; 32]));
+        let mut randomizer = kind.build();
+        (0..len).map(|_| randomizer.next(&mut rng)).collect()
+    }
+
+    #[test]
+    fn bags_contain_every_piece() {
+        for (kind, size) in [
+            (RandomizerKind::SevenBag, 7),
+            (RandomizerKind::FourteenBag, 14),
+        ] {
+            for bag in sequence(&kind, size * 5).chunks(size) {
+                for piece in PIECES {
+                    assert_eq!(bag.iter().filter(|p| **p == piece).count(), size / 7);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deterministic_from_seed() {
+        for kind in [
+            RandomizerKind::SevenBag,
+            RandomizerKind::FourteenBag,
+            RandomizerKind::Uniform,
+            RandomizerKind::Nes,
+            RandomizerKind::History,
+        ] {
+            assert_eq!(sequence(&kind, 100), sequence(&kind, 100));
+        }
+        let fixed = RandomizerKind::Fixed(vec![Mino::T, Mino::I]);
+        assert_eq!(
+            sequence(&fixed, 5),
+            [Mino::T, Mino::I, Mino::T, Mino::I, Mino::T]
+        );
+    }
+
+    #[test]
+    fn history_avoids_repeats() {
+        let pieces = sequence(&RandomizerKind::History, 1000);
+        assert!(![Mino::S, Mino::Z, Mino::O].contains(&pieces[0]));
+        let repeats = pieces.windows(2).filter(|w| w[0] == w[1]).count();
+        assert!(repeats < 10, "{repeats} repeats");
+    }
+
+    #[test]
+    fn fixed_skips_invalid_pieces() {
+        let fixed = RandomizerKind::Fixed(vec![Mino::Empty, Mino::S, Mino::Garbage]);
+        assert_eq!(sequence(&fixed, 3), [Mino::S; 3]);
+        let empty = RandomizerKind::Fixed(vec![Mino::Empty]);
+        assert_eq!(sequence(&empty, 2), [Mino::I; 2]);
+    }
+}
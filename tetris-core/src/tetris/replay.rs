@@ -0,0 +1,207 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{Action, Game, GameConfig, getrandom};
+
+/// Frames between two snapshots kept by the [`ReplayPlayer`] for seeking
+const KEYFRAME_INTERVAL: u32 = 600;
+
+/// The inputs of a game, including garbage received from an opponent, enough to re-simulate it
+/// exactly
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub config: GameConfig,
+    /// Number of recorded frames
+    pub frames: u32,
+    /// Only frames with at least one action, as (frame, actions)
+    inputs: Vec<(u32, Vec<Action>)>,
+    /// Garbage received before a frame, as (frame, lines)
+    #[serde(default)]
+    garbage: Vec<(u32, u8)>,
+}
+
+impl Replay {
+    /// Starts a new recording. A seed is generated if the config has none, so the game has to be
+    /// created from `replay.config`.
+    pub fn new(mut config: GameConfig) -> Self {
+        if config.seed.is_none() {
+            config.seed = Some(getrandom(None).random());
+        }
+        Self {
+            config,
+            frames: 0,
+            inputs: vec![],
+            garbage: vec![],
+        }
+    }
+
+    /// Records the actions passed to [`Game::user_actions`] for the next frame
+    pub fn record(&mut self, actions: &[Action]) {
+        if !actions.is_empty() {
+            self.inputs.push((self.frames, actions.to_vec()));
+        }
+        self.frames += 1;
+    }
+
+    /// Records garbage passed to [`Game::accumulate_garbage`] before the next frame
+    pub fn record_garbage(&mut self, lines: u8) {
+        self.garbage.push((self.frames, lines));
+    }
+
+    /// Garbage received from an opponent, as (frame, lines)
+    pub fn garbage(&self) -> &[(u32, u8)] {
+        &self.garbage
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_cbor::ser::to_vec_packed(self).unwrap_or_default()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> serde_cbor::Result<Self> {
+        serde_cbor::from_slice(bytes)
+    }
+}
+
+/// Re-simulates a [`Replay`] frame by frame
+pub struct ReplayPlayer {
+    replay: Replay,
+    pub game: Game,
+    frame: u32,
+    input_idx: usize,
+    garbage_idx: usize,
+    keyframes: Vec<(Game, usize, usize)>,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        let game = Game::new(replay.config.clone());
+        Self {
+            replay,
+            keyframes: vec![(game.clone(), 0, 0)],
+            game,
+            frame: 0,
+            input_idx: 0,
+            garbage_idx: 0,
+        }
+    }
+
+    pub const fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub const fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Simulates the next frame. Returns `false` if the replay is over.
+    pub fn step(&mut self) -> bool {
+        if self.frame >= self.replay.frames || self.game.done {
+            return false;
+        }
+        while let Some((frame, lines)) = self.replay.garbage.get(self.garbage_idx)
+            && *frame == self.frame
+        {
+            self.game.accumulate_garbage(*lines);
+            self.garbage_idx += 1;
+        }
+        let actions = match self.replay.inputs.get(self.input_idx) {
+            Some((frame, actions)) if *frame == self.frame => {
+                self.input_idx += 1;
+                actions.clone()
+            }
+            _ => vec![],
+        };
+        self.game.user_actions(actions);
+        self.frame += 1;
+        if self.frame.is_multiple_of(KEYFRAME_INTERVAL)
+            && self.keyframes.len() as u32 == self.frame / KEYFRAME_INTERVAL
+        {
+            self.keyframes
+                .push((self.game.clone(), self.input_idx, self.garbage_idx));
+        }
+        true
+    }
+
+    /// Jumps to the given frame, starting from the closest snapshot before it
+    pub fn seek(&mut self, frame: u32) {
+        let frame = frame.min(self.replay.frames);
+        if frame < self.frame || frame - self.frame > KEYFRAME_INTERVAL {
+            let keyframe = ((frame / KEYFRAME_INTERVAL) as usize).min(self.keyframes.len() - 1);
+            let (game, input_idx, garbage_idx) = &self.keyframes[keyframe];
+            self.game = game.clone();
+            self.input_idx = *input_idx;
+            self.garbage_idx = *garbage_idx;
+            self.frame = keyframe as u32 * KEYFRAME_INTERVAL;
+        }
+        while self.frame < frame && self.step() {}
+    }
+
+    /// Simulates the rest of the replay and returns the final state
+    pub fn finish(mut self) -> Game {
+        while self.step() {}
+        self.game
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Replay, ReplayPlayer};
+    use crate::tetris::{Action, Game, GameConfig, GameSettings};
+
+    fn record(frames: u32) -> (Replay, Game) {
+        let mut replay = Replay::new(GameConfig::with_seed(GameSettings::default(), [7; 32]));
+        let mut game = Game::new(replay.config.clone());
+        for frame in 0..frames {
+            if game.done {
+                break;
+            }
+            // spread the pieces over the board
+            let actions = match (frame % 40, frame / 40 % 5) {
+                (0, _) => vec![Action::Cw],
+                (1..=4, 0) => vec![Action::Left],
+                (1..=2, 1) => vec![Action::Left],
+                (1..=2, 3) => vec![Action::Right],
+                (1..=4, 4) => vec![Action::Right],
+                (20, _) => vec![Action::HardDrop],
+                _ => vec![],
+            };
+            if frame % 300 == 0 {
+                replay.record_garbage(2);
+                game.accumulate_garbage(2);
+            }
+            replay.record(&actions);
+            game.user_actions(actions);
+        }
+        (replay, game)
+    }
+
+    #[test]
+    fn replay_matches_game() {
+        let (replay, game) = record(1500);
+        let replay = Replay::from_bytes(&replay.to_bytes()).unwrap();
+        let replayed = ReplayPlayer::new(replay).finish();
+        assert_eq!(replayed.board, game.board);
+        assert_eq!(replayed.score, game.score);
+        assert_eq!(replayed.frame, game.frame);
+    }
+
+    #[test]
+    fn seek_backwards() {
+        let (replay, _) = record(1500);
+        let mut player = ReplayPlayer::new(replay);
+        player.seek(1000);
+        let board = player.game.board.clone();
+        player.seek(200);
+        assert_eq!(player.frame(), 200);
+        player.seek(1000);
+        assert_eq!(player.game.board, board);
+    }
+
+    #[test]
+    fn action_encoding_is_stable() {
+        // packed cbor encodes the variant index, older replays must keep decoding
+        let actions = [Action::Hold, Action::HardDrop, Action::SoftDrop];
+        let bytes = serde_cbor::ser::to_vec_packed(&actions).unwrap();
+        assert_eq!(bytes, [0x83, 4, 5, 6]);
+    }
+}
@@ -0,0 +1,365 @@
+use std::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Direction, Mino, Rotation, Shape, Tetrimino};
+
+/// Decides how pieces look in each rotation state and which kicks are tried when rotating.
+/// Kick offsets point down on the y-axis like the board.
+pub trait RotationSystem: Debug + Send + Sync {
+    /// Cells of a piece in a rotation state, rows from the top
+    fn grid(&self, kind: Mino, rotation: Rotation) -> Shape;
+
+    /// Offsets that are tried in order when rotating
+    fn kicks(&self, kind: Mino, from: Rotation, to: Rotation) -> &'static [(i8, i8)];
+
+    /// Where the box of a piece is placed when it enters the board, before it moves down by one.
+    /// The defaults are the guideline positions for SRS.
+    fn spawn_offset(&self, kind: Mino) -> (i8, i8) {
+        match kind {
+            Mino::Empty | Mino::Garbage => (0, 0),
+            Mino::O => (4, 18),
+            Mino::I => (3, 19),
+            Mino::L | Mino::J | Mino::S | Mino::Z | Mino::T => (3, 18),
+        }
+    }
+
+    /// Position of the center cell of the T piece in its box, the cell the T spin corners are
+    /// around
+    fn t_center(&self, rotation: Rotation) -> (i8, i8) {
+        let grid = self.grid(Mino::T, rotation);
+        grid.cells()
+            .find(|&(x, y)| {
+                let neighbours = [
+                    (x + 1, y),
+                    (x, y + 1),
+                    (x.wrapping_sub(1), y),
+                    (x, y.wrapping_sub(1)),
+                ];
+                neighbours
+                    .iter()
+                    .filter(|&&(x, y)| grid.is_filled(x, y))
+                    .count()
+                    == 3
+            })
+            .map_or((1, 1), |(x, y)| (x as i8, y as i8))
+    }
+
+    /// A piece in spawn orientation at 0, 0
+    fn spawn(&self, kind: Mino) -> Tetrimino {
+        Tetrimino {
+            kind,
+            rotation: Rotation::Zero,
+            grid: self.grid(kind, Rotation::Zero),
+            offset_x: 0,
+            offset_y: 0,
+        }
+    }
+}
+
+/// Selects the [`RotationSystem`] of a game
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RotationSystemKind {
+    #[default]
+    Srs,
+    Ars,
+    Nes,
+    /// SRS pieces without any kicks
+    NoKick,
+}
+
+impl RotationSystemKind {
+    pub const fn system(self) -> &'static dyn RotationSystem {
+        match self {
+            Self::Srs => &Srs,
+            Self::Ars => &Ars,
+            Self::Nes => &NesRotation,
+            Self::NoKick => &NoKick,
+        }
+    }
+}
+
+const NO_KICKS: &[(i8, i8)] = &[(0, 0)];
+
+/// The Super Rotation System of the guideline
+#[derive(Debug, Clone, Copy)]
+pub struct Srs;
+
+impl Srs {
+    const fn spawn_grid(kind: Mino) -> Shape {
+        match kind {
+            Mino::Empty | Mino::Garbage => Shape::parse(&[]),
+            Mino::O => Shape::parse(&["##", "##"]),
+            Mino::I => Shape::parse(&["....", "####", "....", "...."]),
+            Mino::T => Shape::parse(&[".#.", "###", "..."]),
+            Mino::L => Shape::parse(&["..#", "###", "..."]),
+            Mino::J => Shape::parse(&["#..", "###", "..."]),
+            Mino::S => Shape::parse(&[".##", "##.", "..."]),
+            Mino::Z => Shape::parse(&["##.", ".##", "..."]),
+        }
+    }
+
+    fn three_kicks(from: Rotation, to: Rotation) -> &'static [(i8, i8)] {
+        use Rotation as R;
+        match (from, to) {
+            (_, R::Right) => &[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+            (R::Right, _) => &[(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+            (_, R::Left) => &[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+            (R::Left, _) => &[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+            _ => unreachable!("Tried to invalid rotation from: {from:?}, to: {to:?}"),
+        }
+    }
+
+    /// 180 kicks of SRS+, used for every piece
+    fn half_kicks(from: Rotation, to: Rotation) -> &'static [(i8, i8)] {
+        use Rotation as R;
+        match (from, to) {
+            (R::Zero, R::Two) => &[(0, 0), (0, -1), (1, -1), (-1, -1), (1, 0), (-1, 0)],
+            (R::Two, R::Zero) => &[(0, 0), (0, 1), (-1, 1), (1, 1), (-1, 0), (1, 0)],
+            (R::Right, R::Left) => &[(0, 0), (1, 0), (1, -2), (1, -1), (0, -2), (0, -1)],
+            (R::Left, R::Right) => &[(0, 0), (-1, 0), (-1, -2), (-1, -1), (0, -2), (0, -1)],
+            _ => unreachable!("Tried invalid rotation from: {from:?}, to: {to:?}"),
+        }
+    }
+
+    fn i_kicks(from: Rotation, to: Rotation) -> &'static [(i8, i8)] {
+        use Rotation as R;
+        match (from, to) {
+            (R::Zero, R::Right) | (R::Left, R::Two) => &[(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+            (R::Right, R::Zero) | (R::Two, R::Left) => &[(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+            (R::Right, R::Two) | (R::Zero, R::Left) => &[(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+            (R::Two, R::Right) | (R::Left, R::Zero) => &[(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+            _ => unreachable!("Tried invalid rotation from: {from:?}, to: {to:?}"),
+        }
+    }
+}
+
+impl RotationSystem for Srs {
+    fn grid(&self, kind: Mino, rotation: Rotation) -> Shape {
+        let mut grid = Self::spawn_grid(kind);
+        for _ in 0..rotation as u8 {
+            grid = grid.rotate(Direction::Cw);
+        }
+        grid
+    }
+
+    fn kicks(&self, kind: Mino, from: Rotation, to: Rotation) -> &'static [(i8, i8)] {
+        match kind {
+            Mino::Empty | Mino::Garbage | Mino::O => NO_KICKS,
+            _ if from.rotate(Direction::Half) == to => Self::half_kicks(from, to),
+            Mino::J | Mino::L | Mino::S | Mino::Z | Mino::T => Self::three_kicks(from, to),
+            Mino::I => Self::i_kicks(from, to),
+        }
+    }
+}
+
+/// SRS pieces, but rotations only succeed in place
+#[derive(Debug, Clone, Copy)]
+pub struct NoKick;
+
+impl RotationSystem for NoKick {
+    fn grid(&self, kind: Mino, rotation: Rotation) -> Shape {
+        Srs.grid(kind, rotation)
+    }
+
+    fn kicks(&self, _kind: Mino, _from: Rotation, _to: Rotation) -> &'static [(i8, i8)] {
+        NO_KICKS
+    }
+}
+
+/// Arika Rotation System of the Tetris The Grand Master games. Pieces spawn flat side up and
+/// rest on the bottom of their box, only the basic right then left kicks are tried and the I
+/// piece never kicks. The center column rule is not modelled.
+#[derive(Debug, Clone, Copy)]
+pub struct Ars;
+
+impl RotationSystem for Ars {
+    fn grid(&self, kind: Mino, rotation: Rotation) -> Shape {
+        use Rotation as R;
+        let rows: &[&str] = match (kind, rotation) {
+            (Mino::Empty | Mino::Garbage, _) => &[],
+            (Mino::O, _) => &["##", "##"],
+            (Mino::I, R::Zero | R::Two) => &["....", "####", "....", "...."],
+            (Mino::I, R::Right | R::Left) => &["..#.", "..#.", "..#.", "..#."],
+            (Mino::T, R::Zero) => &["...", "###", ".#."],
+            (Mino::T, R::Right) => &[".#.", "##.", ".#."],
+            (Mino::T, R::Two) => &["...", ".#.", "###"],
+            (Mino::T, R::Left) => &[".#.", ".##", ".#."],
+            (Mino::L, R::Zero) => &["...", "###", "#.."],
+            (Mino::L, R::Right) => &["##.", ".#.", ".#."],
+            (Mino::L, R::Two) => &["...", "..#", "###"],
+            (Mino::L, R::Left) => &[".#.", ".#.", ".##"],
+            (Mino::J, R::Zero) => &["...", "###", "..#"],
+            (Mino::J, R::Right) => &[".#.", ".#.", "##."],
+            (Mino::J, R::Two) => &["...", "#..", "###"],
+            (Mino::J, R::Left) => &[".##", ".#.", ".#."],
+            (Mino::S, R::Zero | R::Two) => &["...", ".##", "##."],
+            (Mino::S, R::Right | R::Left) => &["#..", "##.", ".#."],
+            (Mino::Z, R::Zero | R::Two) => &["...", "##.", ".##"],
+            (Mino::Z, R::Right | R::Left) => &["..#", ".##", ".#."],
+        };
+        Shape::parse(rows)
+    }
+
+    fn kicks(&self, kind: Mino, _from: Rotation, _to: Rotation) -> &'static [(i8, i8)] {
+        match kind {
+            Mino::J | Mino::L | Mino::S | Mino::Z | Mino::T => &[(0, 0), (1, 0), (-1, 0)],
+            _ => NO_KICKS,
+        }
+    }
+
+    /// The 3 wide pieces rest on the bottom of their box, one row lower than in SRS
+    fn spawn_offset(&self, kind: Mino) -> (i8, i8) {
+        match kind {
+            Mino::L | Mino::J | Mino::S | Mino::Z | Mino::T => (3, 17),
+            _ => Srs.spawn_offset(kind),
+        }
+    }
+}
+
+/// Rotation of NES Tetris: pieces turn around their center without any kicks, and I, S and Z
+/// only have two states
+#[derive(Debug, Clone, Copy)]
+pub struct NesRotation;
+
+impl RotationSystem for NesRotation {
+    fn grid(&self, kind: Mino, rotation: Rotation) -> Shape {
+        use Rotation as R;
+        let rows: &[&str] = match (kind, rotation) {
+            (Mino::Empty | Mino::Garbage, _) => &[],
+            (Mino::O, _) => &["##", "##"],
+            (Mino::I, R::Zero | R::Two) => &["....", "....", "####", "...."],
+            (Mino::I, R::Right | R::Left) => &["..#.", "..#.", "..#.", "..#."],
+            (Mino::T, R::Zero) => &["...", "###", ".#."],
+            (Mino::T, R::Right) => &[".#.", "##.", ".#."],
+            (Mino::T, R::Two) => &[".#.", "###", "..."],
+            (Mino::T, R::Left) => &[".#.", ".##", ".#."],
+            (Mino::L, R::Zero) => &["...", "###", "#.."],
+            (Mino::L, R::Right) => &["##.", ".#.", ".#."],
+            (Mino::L, R::Two) => &["..#", "###", "..."],
+            (Mino::L, R::Left) => &[".#.", ".#.", ".##"],
+            (Mino::J, R::Zero) => &["...", "###", "..#"],
+            (Mino::J, R::Right) => &[".#.", ".#.", "##."],
+            (Mino::J, R::Two) => &["#..", "###", "..."],
+            (Mino::J, R::Left) => &[".##", ".#.", ".#."],
+            (Mino::S, R::Zero | R::Two) => &["...", ".##", "##."],
+            (Mino::S, R::Right | R::Left) => &[".#.", ".##", "..#"],
+            (Mino::Z, R::Zero | R::Two) => &["...", "##.", ".##"],
+            (Mino::Z, R::Right | R::Left) => &["..#", ".##", ".#."],
+        };
+        Shape::parse(rows)
+    }
+
+    fn kicks(&self, _kind: Mino, _from: Rotation, _to: Rotation) -> &'static [(i8, i8)] {
+        NO_KICKS
+    }
+
+    /// Pieces spawn in the same rows as in SRS, which are one row lower in their boxes
+    fn spawn_offset(&self, kind: Mino) -> (i8, i8) {
+        match kind {
+            Mino::I => (3, 18),
+            Mino::L | Mino::J | Mino::S | Mino::Z | Mino::T => (3, 17),
+            _ => Srs.spawn_offset(kind),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Ars, NesRotation, RotationSystem, RotationSystemKind, Srs};
+    use crate::tetris::{Board, Direction, Mino, Rotation, Tetrimino};
+
+    #[test]
+    fn srs_matches_rotated_grid() {
+        for kind in [Mino::I, Mino::T, Mino::S, Mino::L] {
+            let mut piece = Tetrimino::new(kind, 0, 0);
+            for rotation in [Rotation::Right, Rotation::Two, Rotation::Left] {
+                piece.rotate_grid(Direction::Cw);
+                assert_eq!(piece.grid, Srs.grid(kind, rotation));
+            }
+        }
+    }
+
+    #[test]
+    fn every_state_has_four_cells() {
+        for kind in [
+            RotationSystemKind::Srs,
+            RotationSystemKind::Ars,
+            RotationSystemKind::Nes,
+        ] {
+            let system = kind.system();
+            for mino in [
+                Mino::I,
+                Mino::O,
+                Mino::T,
+                Mino::L,
+                Mino::J,
+                Mino::S,
+                Mino::Z,
+            ] {
+                for rotation in [
+                    Rotation::Zero,
+                    Rotation::Right,
+                    Rotation::Two,
+                    Rotation::Left,
+                ] {
+                    assert_eq!(system.grid(mino, rotation).cells().count(), 4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn only_srs_kicks_off_the_wall() {
+        let board = Board::new();
+        for (kind, kicks) in [
+            (RotationSystemKind::Srs, true),
+            (RotationSystemKind::NoKick, false),
+            (RotationSystemKind::Nes, false),
+        ] {
+            let system = kind.system();
+            let mut piece = Tetrimino {
+                rotation: Rotation::Right,
+                grid: system.grid(Mino::I, Rotation::Right),
+                offset_x: -2,
+                offset_y: 30,
+                ..system.spawn(Mino::I)
+            };
+            assert!(board.can_place(&piece));
+            let kick = board.rotate(&mut piece, system, true, Direction::Cw);
+            assert_eq!(kick.is_some(), kicks, "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn half_rotation_kicks_up_from_floor() {
+        let board = Board::new();
+        let mut piece = Tetrimino::new(Mino::T, 3, 38);
+        assert!(!board.can_move_down(&mut piece));
+        let kick = board.rotate(&mut piece, &Srs, true, Direction::Half);
+        assert_eq!(kick, Some(1));
+        assert_eq!(piece.rotation, Rotation::Two);
+        assert_eq!(piece.offset_y, 37);
+    }
+
+    #[test]
+    fn pieces_spawn_in_the_same_rows() {
+        let bottom = |system: &dyn RotationSystem, kind: Mino| {
+            let (_, y) = system.spawn_offset(kind);
+            let cells = system.grid(kind, Rotation::Zero).cells();
+            cells.map(|(_, row)| row as i8 + y).max()
+        };
+        for kind in [
+            Mino::I,
+            Mino::O,
+            Mino::T,
+            Mino::L,
+            Mino::J,
+            Mino::S,
+            Mino::Z,
+        ] {
+            assert_eq!(bottom(&Ars, kind), bottom(&Srs, kind), "{kind:?}");
+            assert_eq!(bottom(&NesRotation, kind), bottom(&Srs, kind), "{kind:?}");
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use serde::{Deserialize, Serialize};
+
+use super::{GameMode, GameSettings, RandomizerKind, RotationSystemKind};
+
+/// When a game ends other than by topping out
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Goal {
+    #[default]
+    Endless,
+    /// Clear this many lines, as fast as possible
+    Lines(u16),
+    /// Score as much as possible in this many frames
+    Time(u32),
+    /// Start with this many rows of garbage and clear all of them
+    Dig(u8),
+    /// Reach this level, like a marathon to level 15
+    Level(u8),
+}
+
+/// How fast pieces fall on a level
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gravity {
+    /// Frames to wait between falls, 0 falls every frame
+    pub frames: u8,
+    /// Cells to fall at once, 20 reaches the floor instantly (20G)
+    pub cells: u8,
+}
+
+impl Gravity {
+    /// One cell per `frames + 1` frames
+    pub const fn frames(frames: u8) -> Self {
+        Self { frames, cells: 1 }
+    }
+
+    /// `cells` cells every frame
+    pub const fn cells(cells: u8) -> Self {
+        Self { frames: 0, cells }
+    }
+}
+
+/// How the lock delay is reset while a piece rests on the stack
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lockdown {
+    /// Every move or rotation resets the lock delay, so pieces can be kept alive forever
+    Infinity,
+    /// Moves and rotations reset the lock delay this many times, reaching a new lowest row gives
+    /// the resets back
+    MoveReset(u8),
+    /// Only reaching a new lowest row resets the lock delay
+    StepReset,
+    /// Pieces lock once gravity can't move them down, like NES Tetris. The lock delay is not used.
+    Classic,
+}
+
+impl Lockdown {
+    /// Moves and rotations that reset the lock delay on every new lowest row
+    pub const fn resets(self) -> u8 {
+        match self {
+            Self::MoveReset(resets) => resets,
+            Self::Infinity | Self::StepReset | Self::Classic => 0,
+        }
+    }
+}
+
+const MIN_LOCK_DELAY: u8 = 8;
+const MIN_ENTRY_DELAY: u8 = 4;
+
+/// Timings and rules of a game. The modes selectable with [`GameSettings`] are presets, see
+/// [`Ruleset::from`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Ruleset {
+    /// Frames between a piece locking and the next one spawning (ARE)
+    pub entry_delay: u8,
+    /// Frames a piece can rest on the stack before it locks
+    pub lock_delay: u8,
+    pub lockdown: Lockdown,
+    /// Lines to clear per level, 0 stays on level 1 forever
+    pub level_lines: u8,
+    /// Gravity for each level starting at 1, the last entry is used for all higher levels
+    pub gravity: [Gravity; 20],
+    /// Frames the entry and lock delay get shorter with every level after the gravity table
+    pub delay_step: u8,
+    /// Gravity is this many times faster, as if soft drop was always held
+    pub fall_factor: u8,
+    pub randomizer: RandomizerKind,
+    pub rotation: RotationSystemKind,
+    pub goal: Goal,
+}
+
+impl Ruleset {
+    pub const GUIDELINE: Self = Self {
+        entry_delay: 12,
+        lock_delay: 30,
+        lockdown: Lockdown::MoveReset(5),
+        level_lines: 5,
+        gravity: [
+            Gravity::frames(30),
+            Gravity::frames(20),
+            Gravity::frames(15),
+            Gravity::frames(10),
+            Gravity::frames(8),
+            Gravity::frames(6),
+            Gravity::frames(5),
+            Gravity::frames(4),
+            Gravity::frames(3),
+            Gravity::frames(2),
+            Gravity::frames(1),
+            Gravity::frames(1),
+            Gravity::frames(1),
+            Gravity::frames(1),
+            Gravity::frames(1),
+            Gravity::frames(1),
+            Gravity::frames(1),
+            Gravity::frames(1),
+            Gravity::frames(1),
+            Gravity::frames(1),
+        ],
+        delay_step: 0,
+        fall_factor: 1,
+        randomizer: RandomizerKind::SevenBag,
+        rotation: RotationSystemKind::Srs,
+        goal: Goal::Endless,
+    };
+
+    pub const JUPITER: Self = Self {
+        fall_factor: 10,
+        ..Self::GUIDELINE
+    };
+
+    pub const NES: Self = Self {
+        lockdown: Lockdown::Classic,
+        randomizer: RandomizerKind::Nes,
+        rotation: RotationSystemKind::Nes,
+        ..Self::GUIDELINE
+    };
+
+    /// 40 lines on level 1
+    pub const SPRINT: Self = Self {
+        level_lines: 0,
+        goal: Goal::Lines(40),
+        ..Self::GUIDELINE
+    };
+
+    /// 2 minutes of score attack on level 1
+    pub const ULTRA: Self = Self {
+        level_lines: 0,
+        goal: Goal::Time(2 * 60 * 60),
+        ..Self::GUIDELINE
+    };
+
+    /// Dig through 10 rows of cheese garbage
+    pub const CHEESE: Self = Self {
+        level_lines: 0,
+        goal: Goal::Dig(10),
+        ..Self::GUIDELINE
+    };
+
+    /// Marathon that ends on level 15, falling several cells per frame after level 12
+    pub const MARATHON: Self = Self {
+        gravity: [
+            Gravity::frames(30),
+            Gravity::frames(20),
+            Gravity::frames(15),
+            Gravity::frames(10),
+            Gravity::frames(8),
+            Gravity::frames(6),
+            Gravity::frames(5),
+            Gravity::frames(4),
+            Gravity::frames(3),
+            Gravity::frames(2),
+            Gravity::frames(1),
+            Gravity::frames(0),
+            Gravity::cells(2),
+            Gravity::cells(3),
+            Gravity::cells(4),
+            Gravity::cells(6),
+            Gravity::cells(8),
+            Gravity::cells(12),
+            Gravity::cells(16),
+            Gravity::cells(20),
+        ],
+        goal: Goal::Level(15),
+        ..Self::GUIDELINE
+    };
+
+    /// Endless, 20G from level 8 on and the delays get shorter after level 20
+    pub const MASTER: Self = Self {
+        gravity: [
+            Gravity::frames(10),
+            Gravity::frames(5),
+            Gravity::frames(2),
+            Gravity::frames(0),
+            Gravity::cells(2),
+            Gravity::cells(4),
+            Gravity::cells(8),
+            Gravity::cells(20),
+            Gravity::cells(20),
+            Gravity::cells(20),
+            Gravity::cells(20),
+            Gravity::cells(20),
+            Gravity::cells(20),
+            Gravity::cells(20),
+            Gravity::cells(20),
+            Gravity::cells(20),
+            Gravity::cells(20),
+            Gravity::cells(20),
+            Gravity::cells(20),
+            Gravity::cells(20),
+        ],
+        delay_step: 1,
+        ..Self::GUIDELINE
+    };
+
+    pub fn gravity(&self, level: u8) -> Gravity {
+        let idx = usize::from(level.max(1) - 1).min(self.gravity.len() - 1);
+        self.gravity[idx]
+    }
+
+    pub fn lock_delay(&self, level: u8) -> u8 {
+        self.shorten(self.lock_delay, level, MIN_LOCK_DELAY)
+    }
+
+    pub fn entry_delay(&self, level: u8) -> u8 {
+        self.shorten(self.entry_delay, level, MIN_ENTRY_DELAY)
+    }
+
+    /// Applies the delay step for the levels after the gravity table, a delay never gets shorter
+    /// than `min` unless it started out shorter
+    fn shorten(&self, delay: u8, level: u8, min: u8) -> u8 {
+        let levels = level.saturating_sub(self.gravity.len() as u8);
+        delay
+            .saturating_sub(levels.saturating_mul(self.delay_step))
+            .max(min.min(delay))
+    }
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Self::GUIDELINE
+    }
+}
+
+impl From<GameSettings> for Ruleset {
+    fn from(settings: GameSettings) -> Self {
+        let mut ruleset = if settings.nes {
+            Self::NES
+        } else {
+            Self::GUIDELINE
+        };
+        if settings.jupiter {
+            ruleset.fall_factor = Self::JUPITER.fall_factor;
+        }
+        if settings.easy {
+            ruleset.level_lines = 0;
+        }
+        match settings.mode {
+            GameMode::Marathon => {}
+            GameMode::Sprint => {
+                ruleset.level_lines = Self::SPRINT.level_lines;
+                ruleset.goal = Self::SPRINT.goal;
+            }
+            GameMode::Ultra => {
+                ruleset.level_lines = Self::ULTRA.level_lines;
+                ruleset.goal = Self::ULTRA.goal;
+            }
+            GameMode::Cheese => {
+                ruleset.level_lines = Self::CHEESE.level_lines;
+                ruleset.goal = Self::CHEESE.goal;
+            }
+            GameMode::Marathon15 => {
+                ruleset.gravity = Self::MARATHON.gravity;
+                ruleset.goal = Self::MARATHON.goal;
+            }
+            GameMode::Master => {
+                ruleset.gravity = Self::MASTER.gravity;
+                ruleset.delay_step = Self::MASTER.delay_step;
+            }
+        }
+        if settings.random {
+            ruleset.randomizer = RandomizerKind::Uniform;
+        }
+        ruleset
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GameMode, GameSettings, Gravity, Lockdown, Ruleset};
+
+    #[test]
+    fn presets_from_settings() {
+        assert_eq!(Ruleset::from(GameSettings::default()), Ruleset::GUIDELINE);
+        assert_eq!(
+            Ruleset::from(GameSettings::new(
+                true,
+                false,
+                false,
+                false,
+                GameMode::Marathon
+            )),
+            Ruleset::JUPITER
+        );
+        let easy_nes = Ruleset::from(GameSettings::new(
+            false,
+            true,
+            true,
+            false,
+            GameMode::Marathon,
+        ));
+        assert_eq!(easy_nes.lockdown, Lockdown::Classic);
+        assert_eq!(easy_nes.level_lines, 0);
+        assert_eq!(Ruleset::GUIDELINE.gravity(1), Gravity::frames(30));
+        assert_eq!(Ruleset::GUIDELINE.gravity(200), Gravity::frames(1));
+        assert_eq!(Ruleset::MARATHON.gravity(15), Gravity::cells(4));
+    }
+
+    #[test]
+    fn master_delays_shrink() {
+        let master = Ruleset::MASTER;
+        assert_eq!(master.lock_delay(20), master.lock_delay);
+        assert_eq!(master.lock_delay(25), master.lock_delay - 5);
+        assert_eq!(master.entry_delay(25), master.entry_delay - 5);
+        assert_eq!(master.lock_delay(255), 8);
+        assert_eq!(master.entry_delay(255), 4);
+        assert_eq!(
+            Ruleset::GUIDELINE.lock_delay(255),
+            Ruleset::GUIDELINE.lock_delay
+        );
+    }
+}